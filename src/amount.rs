@@ -0,0 +1,40 @@
+/// Decimal places of a SOL amount in lamports.
+pub const SOL_DECIMALS: u8 = 9;
+
+/// Formats an amount of base units as a decimal string with `decimals`
+/// places, trimming trailing zeros: `format_units(-1_500_000, 6)` is `"-1.5"`.
+/// Decimals too large to scale by, which only bogus mints declare, print the
+/// raw base units instead.
+pub fn format_units(raw: i128, decimals: u8) -> String {
+    let sign = if raw < 0 { "-" } else { "" };
+    let raw = raw.unsigned_abs();
+    let Some(scale) = 10u128.checked_pow(decimals as u32) else {
+        return format!("{}{} (base units)", sign, raw);
+    };
+    let whole = raw / scale;
+    let fraction = raw % scale;
+    if fraction == 0 {
+        return format!("{}{}", sign, whole);
+    }
+
+    let fraction = format!("{:0width$}", fraction, width = decimals as usize);
+    format!("{}{}.{}", sign, whole, fraction.trim_end_matches('0'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_base_units() {
+        assert_eq!(format_units(-1_500_000, 6), "-1.5");
+        assert_eq!(format_units(2_000_000_000, SOL_DECIMALS), "2");
+        assert_eq!(format_units(1, SOL_DECIMALS), "0.000000001");
+        assert_eq!(format_units(42, 0), "42");
+    }
+
+    #[test]
+    fn falls_back_to_base_units_for_oversized_decimals() {
+        assert_eq!(format_units(-7, 40), "-7 (base units)");
+    }
+}
@@ -0,0 +1,261 @@
+use std::collections::BTreeMap;
+
+use crate::error::{Error, Result};
+use crate::memo::transaction_memo;
+use crate::models::{SolanaTransactionDetails, TokenBalance, TransactionMeta};
+use crate::transaction::{account_keys, timestamp, transaction_id};
+
+/// Net change of an address's SOL balance in one transaction, read from
+/// `meta.preBalances` and `meta.postBalances`. Unlike parsed transfers it
+/// covers every lamport movement, including fees and program-side moves.
+#[derive(Debug)]
+pub struct SolBalanceDelta {
+    pub transaction_id: String,
+    /// Balance before the transaction, in lamports.
+    pub pre_balance: u64,
+    /// Balance after the transaction, in lamports.
+    pub post_balance: u64,
+    /// Fee in lamports if the address paid it, included in the change.
+    pub fee: Option<u64>,
+    /// Block time in unix seconds.
+    pub timestamp: u64,
+    /// Memo of the transaction, if any.
+    pub memo: Option<String>,
+}
+
+impl SolBalanceDelta {
+    /// Reads the balance change of `address` from a transaction. Returns
+    /// `Ok(None)` if the address isn't one of the transaction's accounts.
+    pub fn from_transaction(
+        transaction: &SolanaTransactionDetails,
+        address: &str,
+    ) -> Result<Option<Self>> {
+        let Some(index) = account_keys(transaction)
+            .iter()
+            .position(|key| key == address)
+        else {
+            return Ok(None);
+        };
+        let meta = transaction_meta(transaction)?;
+        let (Some(&pre_balance), Some(&post_balance)) =
+            (meta.pre_balances.get(index), meta.post_balances.get(index))
+        else {
+            return Err(Error::parse("balances missing for account"));
+        };
+
+        Ok(Some(SolBalanceDelta {
+            transaction_id: transaction_id(transaction)?,
+            pre_balance,
+            post_balance,
+            fee: (index == 0).then_some(meta.fee),
+            timestamp: timestamp(transaction)?,
+            memo: transaction_memo(transaction),
+        }))
+    }
+
+    /// Net change in lamports.
+    pub fn change(&self) -> i128 {
+        self.post_balance as i128 - self.pre_balance as i128
+    }
+}
+
+/// Net change of one token account's balance in one transaction, read from
+/// `meta.preTokenBalances` and `meta.postTokenBalances`.
+#[derive(Debug)]
+pub struct TokenBalanceDelta {
+    pub transaction_id: String,
+    pub mint: String,
+    /// Token account whose balance changed.
+    pub account: String,
+    /// Wallet owning the token account, if the node reported it.
+    pub owner: Option<String>,
+    pub decimals: u8,
+    /// Balance before the transaction, in base units.
+    pub pre_amount: u64,
+    /// Balance after the transaction, in base units.
+    pub post_amount: u64,
+    /// Block time in unix seconds.
+    pub timestamp: u64,
+    /// Memo of the transaction, if any.
+    pub memo: Option<String>,
+}
+
+impl TokenBalanceDelta {
+    /// Reads the changed token balances held by `address` from a
+    /// transaction, only those of `mint` if given. `address`
+    /// matches token accounts it owns as well as a token account given
+    /// directly. Accounts created or closed by the transaction count as zero
+    /// on the side they're missing from.
+    pub fn from_transaction(
+        transaction: &SolanaTransactionDetails,
+        address: &str,
+        mint: Option<&str>,
+    ) -> Result<Vec<Self>> {
+        // Without meta the node recorded no token balances to report.
+        let Some(meta) = &transaction.meta else {
+            return Ok(Vec::new());
+        };
+        let keys = account_keys(transaction);
+
+        // Pair pre and post balances by account index.
+        let mut balances: BTreeMap<usize, (Option<&TokenBalance>, Option<&TokenBalance>)> =
+            BTreeMap::new();
+        for balance in &meta.pre_token_balances {
+            balances.entry(balance.account_index).or_default().0 = Some(balance);
+        }
+        for balance in &meta.post_token_balances {
+            balances.entry(balance.account_index).or_default().1 = Some(balance);
+        }
+
+        let mut changes = Vec::new();
+        for (account_index, (pre, post)) in balances {
+            let Some(balance) = post.or(pre) else {
+                continue;
+            };
+            let account = keys
+                .get(account_index)
+                .ok_or_else(|| Error::parse("token account index out of range"))?;
+            let held = balance.owner.as_deref() == Some(address) || account == address;
+            if mint.is_some_and(|mint| balance.mint != mint) || !held {
+                continue;
+            }
+
+            let pre_amount = pre.map(raw_amount).transpose()?.unwrap_or(0);
+            let post_amount = post.map(raw_amount).transpose()?.unwrap_or(0);
+            if pre_amount != post_amount {
+                changes.push((account, balance, pre_amount, post_amount));
+            }
+        }
+        // Only transactions that changed a balance need a timestamp, so
+        // unrelated ones without a block time don't fail.
+        if changes.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let transaction_id = transaction_id(transaction)?;
+        let timestamp = timestamp(transaction)?;
+        let memo = transaction_memo(transaction);
+        Ok(changes
+            .into_iter()
+            .map(
+                |(account, balance, pre_amount, post_amount)| TokenBalanceDelta {
+                    transaction_id: transaction_id.clone(),
+                    mint: balance.mint.clone(),
+                    account: account.clone(),
+                    owner: balance.owner.clone(),
+                    decimals: balance.ui_token_amount.decimals,
+                    pre_amount,
+                    post_amount,
+                    timestamp,
+                    memo: memo.clone(),
+                },
+            )
+            .collect())
+    }
+
+    /// Net change in base units.
+    pub fn change(&self) -> i128 {
+        self.post_amount as i128 - self.pre_amount as i128
+    }
+}
+
+fn transaction_meta(transaction: &SolanaTransactionDetails) -> Result<&TransactionMeta> {
+    transaction
+        .meta
+        .as_ref()
+        .ok_or_else(|| Error::parse("meta not found"))
+}
+
+fn raw_amount(balance: &TokenBalance) -> Result<u64> {
+    balance
+        .ui_token_amount
+        .raw_amount()
+        .ok_or_else(|| Error::parse("invalid token amount"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{system_transfer, token_balance, transaction};
+    use crate::transaction::parse_transaction;
+    use serde_json::{json, Value};
+
+    /// Alice pays the fee and sends Bob 1.5 MintA into a new token account;
+    /// she also holds 2 MintB that don't change.
+    fn token_transfer() -> Value {
+        let mut json = transaction(
+            "Sig1",
+            Some(100),
+            &["Alice", "AliceAta", "BobAta", "AliceMintB"],
+            Vec::new(),
+        );
+        json["meta"]["preTokenBalances"] = json!([
+            token_balance(1, "MintA", Some("Alice"), 5_000_000, 6),
+            token_balance(3, "MintB", Some("Alice"), 2_000, 3),
+        ]);
+        json["meta"]["postTokenBalances"] = json!([
+            token_balance(1, "MintA", Some("Alice"), 3_500_000, 6),
+            token_balance(2, "MintA", Some("Bob"), 1_500_000, 6),
+            token_balance(3, "MintB", Some("Alice"), 2_000, 3),
+        ]);
+        json
+    }
+
+    fn token_deltas(json: &Value, address: &str, mint: Option<&str>) -> Vec<TokenBalanceDelta> {
+        TokenBalanceDelta::from_transaction(&parse_transaction(json).unwrap(), address, mint)
+            .unwrap()
+    }
+
+    #[test]
+    fn reads_sol_balance_change_and_fee() {
+        let mut json = transaction(
+            "Sig1",
+            Some(100),
+            &["Alice", "Bob"],
+            vec![system_transfer("Alice", "Bob", 1_000)],
+        );
+        json["meta"]["preBalances"] = json!([10_000, 0]);
+        json["meta"]["postBalances"] = json!([4_000, 1_000]);
+        let transaction = parse_transaction(&json).unwrap();
+
+        let alice = SolBalanceDelta::from_transaction(&transaction, "Alice")
+            .unwrap()
+            .unwrap();
+        assert_eq!(alice.change(), -6_000);
+        assert_eq!(alice.fee, Some(5000));
+        let bob = SolBalanceDelta::from_transaction(&transaction, "Bob")
+            .unwrap()
+            .unwrap();
+        assert_eq!(bob.change(), 1_000);
+        assert_eq!(bob.fee, None);
+        assert!(SolBalanceDelta::from_transaction(&transaction, "Carol")
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn reads_changed_token_balances_of_owned_accounts() {
+        let json = token_transfer();
+
+        let alice = token_deltas(&json, "Alice", None);
+        assert_eq!(alice.len(), 1);
+        assert_eq!(alice[0].account, "AliceAta");
+        assert_eq!(alice[0].change(), -1_500_000);
+
+        // Bob's account is new, so it had a zero balance before.
+        let bob = token_deltas(&json, "Bob", Some("MintA"));
+        assert_eq!((bob[0].pre_amount, bob[0].post_amount), (0, 1_500_000));
+        // A token account given directly matches itself.
+        assert_eq!(token_deltas(&json, "BobAta", None).len(), 1);
+        assert!(token_deltas(&json, "Alice", Some("MintB")).is_empty());
+    }
+
+    #[test]
+    fn unrelated_transactions_need_no_block_time() {
+        let mut json = token_transfer();
+        json["blockTime"] = Value::Null;
+        assert!(token_deltas(&json, "Carol", None).is_empty());
+        json["meta"] = Value::Null;
+        assert!(token_deltas(&json, "Alice", None).is_empty());
+    }
+}
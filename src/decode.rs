@@ -0,0 +1,625 @@
+use serde::Deserialize;
+use serde_json::Value;
+use solana_sdk::bs58;
+use solana_sdk::program_utils::limited_deserialize;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::system_instruction::SystemInstruction as SystemProgramInstruction;
+use solana_sdk::{compute_budget, system_program};
+use solana_transaction_status::{EncodedTransaction, UiCompiledInstruction};
+use spl_token::instruction::{AuthorityType, TokenInstruction as TokenProgramInstruction};
+
+use crate::amount::format_units;
+use crate::error::{Error, Result};
+use crate::models::{
+    AccountKey, AddressTableLookup, InnerInstructions, Instruction, Message, ParsedInfo,
+    ParsedInstruction, PartiallyDecodedInstruction, SolanaTransactionDetails, SystemInstruction,
+    TokenInstruction, Transaction, TransactionMeta, TransactionVersion, UiTokenAmount,
+};
+
+/// Program ids of SPL Memo v1 and v3.
+const MEMO_PROGRAM_IDS: [&str; 2] = [
+    "Memo1UhkJRfHyvLMcVucJwxXeuD728EqVDDwQDxFMNo",
+    "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr",
+];
+
+/// A `getTransaction` result in `base64` (or `base58`) encoding.
+#[derive(Deserialize)]
+struct BinaryTransactionDetails {
+    slot: u64,
+    #[serde(rename = "blockTime")]
+    block_time: Option<i64>,
+    meta: Option<Value>,
+    transaction: EncodedTransaction,
+    version: Option<TransactionVersion>,
+}
+
+/// Inner instructions as binary encodings report them: account indexes and
+/// base58 data.
+#[derive(Deserialize)]
+struct CompiledInnerInstructions {
+    index: usize,
+    instructions: Vec<UiCompiledInstruction>,
+}
+
+/// Decodes a binary-encoded `getTransaction` result into the same model
+/// `jsonParsed` results deserialize into. System, SPL Token, Token-2022,
+/// associated token account, compute budget and memo instructions are
+/// decoded locally; other instructions are left partially decoded, as
+/// `jsonParsed` leaves programs the node doesn't know.
+pub fn decode_transaction(json: &Value) -> Result<SolanaTransactionDetails> {
+    let details = BinaryTransactionDetails::deserialize(json).map_err(|error| Error::Parse {
+        message: "unexpected getTransaction result".to_string(),
+        source: Some(error),
+    })?;
+    let versioned = details
+        .transaction
+        .decode()
+        .ok_or_else(|| Error::parse("transaction is not a valid binary encoding"))?;
+
+    let (meta, inner_instructions) = match details.meta {
+        Some(mut meta) => {
+            let inner = meta
+                .as_object_mut()
+                .and_then(|meta| meta.remove("innerInstructions"))
+                .unwrap_or_default();
+            let inner = Option::<Vec<CompiledInnerInstructions>>::deserialize(inner)?;
+            (
+                Some(TransactionMeta::deserialize(meta)?),
+                inner.unwrap_or_default(),
+            )
+        }
+        None => (None, Vec::new()),
+    };
+
+    // Loaded addresses follow the static keys, writable ones first.
+    let message = &versioned.message;
+    let mut account_keys: Vec<AccountKey> = message
+        .static_account_keys()
+        .iter()
+        .enumerate()
+        .map(|(index, key)| AccountKey {
+            pubkey: key.to_string(),
+            signer: message.is_signer(index),
+            writable: message.is_maybe_writable(index),
+            source: Some("transaction".to_string()),
+        })
+        .collect();
+    if let Some(meta) = &meta {
+        let loaded = &meta.loaded_addresses;
+        for (addresses, writable) in [(&loaded.writable, true), (&loaded.readonly, false)] {
+            account_keys.extend(addresses.iter().map(|address| AccountKey {
+                pubkey: address.clone(),
+                signer: false,
+                writable,
+                source: Some("lookupTable".to_string()),
+            }));
+        }
+    }
+    let keys = account_keys
+        .iter()
+        .map(|key| {
+            key.pubkey
+                .parse()
+                .map_err(|_| Error::parse("invalid account key"))
+        })
+        .collect::<Result<Vec<Pubkey>>>()?;
+
+    let instructions = message
+        .instructions()
+        .iter()
+        .map(|instruction| {
+            decode_instruction(
+                &keys,
+                instruction.program_id_index,
+                &instruction.accounts,
+                &instruction.data,
+                None,
+            )
+        })
+        .collect::<Result<_>>()?;
+
+    let inner_instructions = inner_instructions
+        .into_iter()
+        .map(|group| {
+            let instructions = group
+                .instructions
+                .iter()
+                .map(|instruction| {
+                    let data = bs58::decode(&instruction.data)
+                        .into_vec()
+                        .map_err(|_| Error::parse("inner instruction data is not base58"))?;
+                    decode_instruction(
+                        &keys,
+                        instruction.program_id_index,
+                        &instruction.accounts,
+                        &data,
+                        instruction.stack_height,
+                    )
+                })
+                .collect::<Result<_>>()?;
+            Ok(InnerInstructions {
+                index: group.index,
+                instructions,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(SolanaTransactionDetails {
+        slot: details.slot,
+        block_time: details.block_time,
+        meta: meta.map(|meta| TransactionMeta {
+            inner_instructions,
+            ..meta
+        }),
+        transaction: Transaction {
+            signatures: versioned
+                .signatures
+                .iter()
+                .map(|signature| signature.to_string())
+                .collect(),
+            message: Message {
+                account_keys,
+                recent_blockhash: message.recent_blockhash().to_string(),
+                instructions,
+                address_table_lookups: message.address_table_lookups().map(|lookups| {
+                    lookups
+                        .iter()
+                        .map(|lookup| AddressTableLookup {
+                            account_key: lookup.account_key.to_string(),
+                            writable_indexes: lookup.writable_indexes.clone(),
+                            readonly_indexes: lookup.readonly_indexes.clone(),
+                        })
+                        .collect()
+                }),
+            },
+        },
+        version: details.version,
+    })
+}
+
+/// Resolves a compiled instruction's account indexes and decodes it if its
+/// program is one this crate knows.
+fn decode_instruction(
+    keys: &[Pubkey],
+    program_id_index: u8,
+    account_indexes: &[u8],
+    data: &[u8],
+    stack_height: Option<u32>,
+) -> Result<Instruction> {
+    let key = |index: u8| {
+        keys.get(index as usize)
+            .ok_or_else(|| Error::parse("account index out of range"))
+    };
+    let program_id = key(program_id_index)?;
+    let accounts = account_indexes
+        .iter()
+        .map(|&index| key(index).copied())
+        .collect::<Result<Vec<Pubkey>>>()?;
+
+    let parsed = if *program_id == system_program::id() {
+        decode_system(&accounts, data).map(|parsed| ("system", parsed))
+    } else if *program_id == spl_token::id() {
+        decode_token(&accounts, data).map(|parsed| ("spl-token", parsed))
+    } else if *program_id == spl_token_2022::id() {
+        decode_token(&accounts, data).map(|parsed| ("spl-token-2022", parsed))
+    } else if *program_id == spl_associated_token_account::id() {
+        decode_associated_token_account(&accounts, data)
+            .map(|parsed| ("spl-associated-token-account", parsed))
+    } else if *program_id == compute_budget::id() {
+        decode_compute_budget(data).map(|parsed| ("compute-budget", parsed))
+    } else if MEMO_PROGRAM_IDS.contains(&program_id.to_string().as_str()) {
+        String::from_utf8(data.to_vec())
+            .ok()
+            .map(|memo| ("spl-memo", ParsedInfo::Memo(memo)))
+    } else {
+        None
+    };
+
+    Ok(match parsed {
+        Some((program, parsed)) => Instruction::Parsed(ParsedInstruction {
+            program: program.to_string(),
+            program_id: program_id.to_string(),
+            parsed,
+            stack_height,
+        }),
+        None => Instruction::PartiallyDecoded(PartiallyDecodedInstruction {
+            program_id: program_id.to_string(),
+            accounts: accounts.iter().map(Pubkey::to_string).collect(),
+            data: bs58::encode(data).into_string(),
+            stack_height,
+        }),
+    })
+}
+
+/// Decodes the system program instructions that move lamports.
+fn decode_system(accounts: &[Pubkey], data: &[u8]) -> Option<ParsedInfo> {
+    let account = |index: usize| accounts.get(index).map(Pubkey::to_string);
+    let instruction = match limited_deserialize(data).ok()? {
+        SystemProgramInstruction::Transfer { lamports } => SystemInstruction::Transfer {
+            source: account(0)?,
+            destination: account(1)?,
+            lamports,
+        },
+        SystemProgramInstruction::TransferWithSeed { lamports, .. } => {
+            SystemInstruction::TransferWithSeed {
+                source: account(0)?,
+                destination: account(2)?,
+                lamports,
+            }
+        }
+        SystemProgramInstruction::CreateAccount { lamports, .. } => {
+            SystemInstruction::CreateAccount {
+                source: account(0)?,
+                new_account: account(1)?,
+                lamports,
+            }
+        }
+        SystemProgramInstruction::CreateAccountWithSeed { lamports, .. } => {
+            SystemInstruction::CreateAccountWithSeed {
+                source: account(0)?,
+                new_account: account(1)?,
+                lamports,
+            }
+        }
+        SystemProgramInstruction::WithdrawNonceAccount(lamports) => {
+            SystemInstruction::WithdrawFromNonce {
+                nonce_account: account(0)?,
+                destination: account(1)?,
+                lamports,
+            }
+        }
+        _ => return None,
+    };
+    Some(ParsedInfo::System(instruction))
+}
+
+/// Decodes SPL Token instructions. Token-2022 shares their layout, so its
+/// instructions decode the same way apart from its extensions.
+fn decode_token(accounts: &[Pubkey], data: &[u8]) -> Option<ParsedInfo> {
+    let account = |index: usize| accounts.get(index).map(Pubkey::to_string);
+    let instruction = match TokenProgramInstruction::unpack(data).ok()? {
+        TokenProgramInstruction::Transfer { amount } => TokenInstruction::Transfer {
+            source: account(0)?,
+            destination: account(1)?,
+            amount,
+        },
+        TokenProgramInstruction::TransferChecked { amount, decimals } => {
+            TokenInstruction::TransferChecked {
+                source: account(0)?,
+                mint: account(1)?,
+                destination: account(2)?,
+                token_amount: token_amount(amount, decimals),
+            }
+        }
+        TokenProgramInstruction::MintTo { amount } => TokenInstruction::MintTo {
+            mint: account(0)?,
+            account: account(1)?,
+            amount,
+        },
+        TokenProgramInstruction::MintToChecked { amount, decimals } => {
+            TokenInstruction::MintToChecked {
+                mint: account(0)?,
+                account: account(1)?,
+                token_amount: token_amount(amount, decimals),
+            }
+        }
+        TokenProgramInstruction::Burn { amount } => TokenInstruction::Burn {
+            account: account(0)?,
+            mint: account(1)?,
+            amount,
+        },
+        TokenProgramInstruction::BurnChecked { amount, decimals } => {
+            TokenInstruction::BurnChecked {
+                account: account(0)?,
+                mint: account(1)?,
+                token_amount: token_amount(amount, decimals),
+            }
+        }
+        TokenProgramInstruction::CloseAccount => TokenInstruction::CloseAccount {
+            account: account(0)?,
+            destination: account(1)?,
+        },
+        TokenProgramInstruction::Approve { amount } => TokenInstruction::Approve {
+            source: account(0)?,
+            delegate: account(1)?,
+            amount,
+        },
+        TokenProgramInstruction::ApproveChecked { amount, decimals } => {
+            TokenInstruction::ApproveChecked {
+                source: account(0)?,
+                mint: account(1)?,
+                delegate: account(2)?,
+                token_amount: token_amount(amount, decimals),
+            }
+        }
+        TokenProgramInstruction::Revoke => TokenInstruction::Revoke {
+            source: account(0)?,
+        },
+        TokenProgramInstruction::SetAuthority {
+            authority_type,
+            new_authority,
+        } => {
+            // Account owner and close authorities belong to token accounts,
+            // the others to mints.
+            let target = account(0)?;
+            let (account, mint, authority_type) = match authority_type {
+                AuthorityType::AccountOwner => (Some(target), None, "accountOwner"),
+                AuthorityType::CloseAccount => (Some(target), None, "closeAccount"),
+                AuthorityType::MintTokens => (None, Some(target), "mintTokens"),
+                AuthorityType::FreezeAccount => (None, Some(target), "freezeAccount"),
+            };
+            TokenInstruction::SetAuthority {
+                account,
+                mint,
+                new_authority: Option::from(new_authority).map(|key: Pubkey| key.to_string()),
+                authority_type: authority_type.to_string(),
+            }
+        }
+        _ => return None,
+    };
+    Some(ParsedInfo::Token(instruction))
+}
+
+/// The `tokenAmount` of a `*Checked` instruction, as `jsonParsed` reports it.
+fn token_amount(amount: u64, decimals: u8) -> UiTokenAmount {
+    let ui_amount_string = format_units(amount as i128, decimals);
+    UiTokenAmount {
+        amount: amount.to_string(),
+        decimals,
+        ui_amount: ui_amount_string.parse().ok(),
+        ui_amount_string,
+    }
+}
+
+/// Decodes associated token account instructions into the shape
+/// `jsonParsed` gives them.
+fn decode_associated_token_account(accounts: &[Pubkey], data: &[u8]) -> Option<ParsedInfo> {
+    let account = |index: usize| accounts.get(index).map(Pubkey::to_string);
+    let (instruction_type, info) = match data {
+        // Instruction data was empty before `createIdempotent` was added.
+        [] | [0] | [1] => {
+            let instruction_type = if data == [1] {
+                "createIdempotent"
+            } else {
+                "create"
+            };
+            let info = serde_json::json!({
+                "source": account(0)?,
+                "account": account(1)?,
+                "wallet": account(2)?,
+                "mint": account(3)?,
+                "systemProgram": account(4)?,
+                "tokenProgram": account(5)?,
+            });
+            (instruction_type, info)
+        }
+        [2] => {
+            let info = serde_json::json!({
+                "nestedSource": account(0)?,
+                "nestedMint": account(1)?,
+                "destination": account(2)?,
+                "nestedOwner": account(3)?,
+                "ownerMint": account(4)?,
+                "wallet": account(5)?,
+                "tokenProgram": account(6)?,
+            });
+            ("recoverNested", info)
+        }
+        _ => return None,
+    };
+    Some(ParsedInfo::Other(
+        serde_json::json!({ "type": instruction_type, "info": info }),
+    ))
+}
+
+/// Decodes compute budget instructions, which `jsonParsed` leaves raw.
+fn decode_compute_budget(data: &[u8]) -> Option<ParsedInfo> {
+    let (&tag, rest) = data.split_first()?;
+    let u32_arg = || Some(u32::from_le_bytes(rest.try_into().ok()?));
+    let (instruction_type, info) = match tag {
+        1 => (
+            "requestHeapFrame",
+            serde_json::json!({ "bytes": u32_arg()? }),
+        ),
+        2 => (
+            "setComputeUnitLimit",
+            serde_json::json!({ "units": u32_arg()? }),
+        ),
+        3 => {
+            let micro_lamports = u64::from_le_bytes(rest.try_into().ok()?);
+            (
+                "setComputeUnitPrice",
+                serde_json::json!({ "microLamports": micro_lamports }),
+            )
+        }
+        4 => (
+            "setLoadedAccountsDataSizeLimit",
+            serde_json::json!({ "bytes": u32_arg()? }),
+        ),
+        _ => return None,
+    };
+    Some(ParsedInfo::Other(
+        serde_json::json!({ "type": instruction_type, "info": info }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::{account_keys, instructions, parse_transaction, token_accounts};
+
+    const PAYER: &str = "GmaDrppBC7P5ARKV8g3djiwP89vz1jLK23V2GBjuAEGB";
+    const BOB: &str = "4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi";
+    const SOURCE: &str = "CktRuQ2mttgRGkXJtyksdKHjUdc2C4TgDzyB98oEzy8";
+    const DESTINATION: &str = "GgBaCs3NCBuZN12kCJgAW63ydqohFkHEdfdEXBPzLHq";
+    const MINT: &str = "8qbHbw2BbbTHBW1sbeqakYXVKRQM8Ne7pLK7m6CVfeR";
+
+    /// A base64 v0 transaction: a compute unit price, a SOL transfer, a
+    /// `transferChecked` whose destination and mint come from a lookup
+    /// table, a memo and an unknown program, plus an inner token transfer.
+    fn base64_fixture() -> Value {
+        serde_json::json!({
+            "slot": 250000000,
+            "blockTime": 1700000000,
+            "version": 0,
+            "meta": {
+                "err": null,
+                "fee": 5000,
+                "preBalances": [],
+                "postBalances": [],
+                "innerInstructions": [{
+                    "index": 2,
+                    "instructions": [{
+                        "programIdIndex": 7,
+                        "accounts": [2, 8, 0],
+                        "data": "3ExsepDFFTsu",
+                        "stackHeight": 2
+                    }]
+                }],
+                "logMessages": [],
+                "preTokenBalances": [{
+                    "accountIndex": 2,
+                    "mint": MINT,
+                    "owner": PAYER,
+                    "uiTokenAmount": {
+                        "amount": "5000000",
+                        "decimals": 6,
+                        "uiAmount": 5.0,
+                        "uiAmountString": "5"
+                    }
+                }],
+                "postTokenBalances": [],
+                "loadedAddresses": {
+                    "writable": [DESTINATION],
+                    "readonly": [MINT]
+                }
+            },
+            "transaction": [
+                concat!(
+                    "AR3kBn4V4sOkTHZVQxOETSDZbuYC5Lq8ESsA9XB+SiIRThRdYComdQIM+3ZksJMZovOxo1NP+nmz",
+                    "kP4edbAWJgSAAQAFCOpKbGPinFIKvvVQexMuxfmVR3auvr57kkIe6mkURtIsAQEBAQEBAQEBAQEB",
+                    "AQEBAQEBAQEBAQEBAQEBAQEBAQEDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwAAAAAA",
+                    "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAwZGb+UhFzL/7K26csOb57yM5bvF9xJrLEObOkAA",
+                    "AAAFSlNamSkhBk0k6HFg2jh8fDW13bySu4HkH6hAQQVEjQYGBgYGBgYGBgYGBgYGBgYGBgYGBgYG",
+                    "BgYGBgYGBgYGBt324ddloZPZy+FGzut5rBy0he1fWzeROoz1hX7/AKkJCQkJCQkJCQkJCQkJCQkJ",
+                    "CQkJCQkJCQkJCQkJCQkJCQUEAAkD6AMAAAAAAAADAgABDAIAAACIEwAAAAAAAAcEAgkIAAoMYOMW",
+                    "AAAAAAAGBQAGSU5WLTQyBgADAQIDAQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFAQAB",
+                    "AQ==",
+                ),
+                "base64"
+            ]
+        })
+    }
+
+    #[test]
+    fn decodes_base64_v0_transaction() {
+        let transaction = parse_transaction(&base64_fixture()).unwrap();
+
+        assert_eq!(
+            transaction.transaction.signatures,
+            ["bfN92p2z8qNPkUSqe3iYQQZGNpb6Gprox5Tv4bLYX765WQTRNNtT6ezndo1EbFpjZJaxjJazmdRsKJJFhS7jJ5h"]
+        );
+        assert_eq!(transaction.block_time, Some(1700000000));
+        let lookups = transaction
+            .transaction
+            .message
+            .address_table_lookups
+            .as_ref()
+            .unwrap();
+        assert_eq!(lookups[0].writable_indexes, [0]);
+        assert_eq!(lookups[0].readonly_indexes, [1]);
+
+        let keys = account_keys(&transaction);
+        assert_eq!(keys.len(), 10);
+        assert_eq!(keys[0], PAYER);
+        assert_eq!(keys[8..], [DESTINATION, MINT]);
+        let message_keys = &transaction.transaction.message.account_keys;
+        assert!(message_keys[0].signer && message_keys[0].writable);
+        assert_eq!(message_keys[9].source.as_deref(), Some("lookupTable"));
+
+        let instructions = instructions(&transaction);
+        let decoded: Vec<(&str, Option<&ParsedInfo>)> = instructions
+            .iter()
+            .map(|instruction_ref| match instruction_ref.instruction {
+                Instruction::Parsed(parsed) => (parsed.program.as_str(), Some(&parsed.parsed)),
+                Instruction::PartiallyDecoded(_) => ("", None),
+            })
+            .collect();
+
+        match decoded[0] {
+            ("compute-budget", Some(ParsedInfo::Other(value))) => {
+                assert_eq!(value["type"], "setComputeUnitPrice");
+                assert_eq!(value["info"]["microLamports"], 1000);
+            }
+            other => panic!("unexpected instruction {:?}", other),
+        }
+        match decoded[1] {
+            (
+                "system",
+                Some(ParsedInfo::System(SystemInstruction::Transfer {
+                    source,
+                    destination,
+                    lamports,
+                })),
+            ) => {
+                assert_eq!((source.as_str(), destination.as_str()), (PAYER, BOB));
+                assert_eq!(*lamports, 5000);
+            }
+            other => panic!("unexpected instruction {:?}", other),
+        }
+        match decoded[2] {
+            (
+                "spl-token",
+                Some(ParsedInfo::Token(TokenInstruction::TransferChecked {
+                    source,
+                    destination,
+                    mint,
+                    token_amount,
+                })),
+            ) => {
+                assert_eq!(source, SOURCE);
+                assert_eq!(destination, DESTINATION);
+                assert_eq!(mint, MINT);
+                assert_eq!(token_amount.amount, "1500000");
+                assert_eq!(token_amount.decimals, 6);
+                assert_eq!(token_amount.ui_amount_string, "1.5");
+            }
+            other => panic!("unexpected instruction {:?}", other),
+        }
+        assert!(matches!(
+            decoded[3],
+            (
+                "spl-token",
+                Some(ParsedInfo::Token(TokenInstruction::Transfer {
+                    amount: 9,
+                    ..
+                }))
+            )
+        ));
+        assert_eq!(instructions[3].index, 2);
+        assert_eq!(instructions[3].stack_height, 2);
+        assert!(matches!(
+            decoded[4],
+            ("spl-memo", Some(ParsedInfo::Memo(memo))) if memo == "INV-42"
+        ));
+        match instructions[5].instruction {
+            Instruction::PartiallyDecoded(instruction) => {
+                assert_eq!(instruction.data, bs58::encode([1, 2, 3]).into_string());
+            }
+            other => panic!("unexpected instruction {:?}", other),
+        }
+
+        let token_accounts = token_accounts(&transaction);
+        assert_eq!(token_accounts[SOURCE].mint, MINT);
+    }
+
+    #[test]
+    fn rejects_undecodable_transaction() {
+        let mut json = base64_fixture();
+        json["transaction"][0] = "bm90IGEgdHJhbnNhY3Rpb24=".into();
+        assert!(matches!(
+            decode_transaction(&json),
+            Err(Error::Parse { .. })
+        ));
+    }
+}
@@ -0,0 +1,115 @@
+use solana_client::client_error::ClientError;
+use solana_sdk::program_error::ProgramError;
+use solana_sdk::signer::SignerError;
+use std::fmt;
+
+use crate::rpc_error::RpcError;
+
+/// Errors returned by the library.
+#[derive(Debug)]
+pub enum Error {
+    /// The HTTP request couldn't be sent or its response couldn't be read.
+    Transport(reqwest::Error),
+    /// The node answered with a JSON-RPC error or a non-success HTTP status.
+    Rpc(RpcError),
+    /// A `solana_client` call made while sending a transaction failed.
+    Client(Box<ClientError>),
+    /// A response or transaction didn't have the expected shape.
+    Parse {
+        message: String,
+        source: Option<serde_json::Error>,
+    },
+    /// A transaction couldn't be built or signed.
+    Signing(SignerError),
+    /// An argument was rejected before anything was sent.
+    Validation(String),
+    /// Reading or writing a local file failed.
+    Io(std::io::Error),
+}
+
+/// Result type used throughout the library.
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// A parse error without an underlying serde error.
+    pub fn parse(message: impl Into<String>) -> Self {
+        Error::Parse {
+            message: message.into(),
+            source: None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Wrapped errors are reported through `source()`.
+        match self {
+            Error::Transport(_) => write!(f, "transport error"),
+            Error::Rpc(_) => write!(f, "RPC error"),
+            Error::Client(_) => write!(f, "client error"),
+            Error::Parse { message, .. } => write!(f, "parse error: {}", message),
+            Error::Signing(_) => write!(f, "signing error"),
+            Error::Validation(message) => write!(f, "invalid input: {}", message),
+            Error::Io(_) => write!(f, "I/O error"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Transport(error) => Some(error),
+            Error::Rpc(error) => Some(error),
+            Error::Client(error) => Some(error.as_ref()),
+            Error::Parse { source, .. } => source.as_ref().map(|error| error as _),
+            Error::Signing(error) => Some(error),
+            Error::Validation(_) => None,
+            Error::Io(error) => Some(error),
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(error: reqwest::Error) -> Self {
+        Error::Transport(error)
+    }
+}
+
+impl From<RpcError> for Error {
+    fn from(error: RpcError) -> Self {
+        Error::Rpc(error)
+    }
+}
+
+impl From<ClientError> for Error {
+    fn from(error: ClientError) -> Self {
+        Error::Client(Box::new(error))
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Error::Parse {
+            message: "invalid JSON".to_string(),
+            source: Some(error),
+        }
+    }
+}
+
+impl From<SignerError> for Error {
+    fn from(error: SignerError) -> Self {
+        Error::Signing(error)
+    }
+}
+
+impl From<ProgramError> for Error {
+    fn from(error: ProgramError) -> Self {
+        Error::Validation(error.to_string())
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error)
+    }
+}
@@ -1,95 +1,669 @@
+use crate::balance_delta::TokenBalanceDelta;
+use crate::error::Result;
 use crate::fungible_token_transfer::is_fungible_token_transaction;
-use crate::fungible_token_transfer::FungibleTokenTransfer;
-use crate::models::RpcResponse;
+use crate::fungible_token_transfer::{FungibleTokenTransfer, TokenEventKind};
+use crate::history::{
+    fetch_signatures, fetch_transactions, parse_history_transaction, FetchOptions, ParsedHistory,
+    SignaturePaging,
+};
 use crate::models::SolanaSignature;
-use chrono::{TimeZone, Utc};
-use prettytable::{row, Cell, Row, Table};
-use reqwest::blocking::Client;
+use crate::rpc::SolanaRpc;
+use log::debug;
 use serde_json::Value;
-use std::error::Error;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
-pub fn print_fungible_transfer_history(
-    solana_rpc_url: &str,
+/// Fetches the token transfers in the transaction history of a given Solana
+/// address and its token accounts, newest transaction first and in
+/// instruction order within a transaction. Without `token_mint`, transfers
+/// of every mint are returned. Token account owners missing from the
+/// transactions' token balances are looked up with `getMultipleAccounts`.
+/// Transactions that can't be parsed are returned in `skipped`.
+pub async fn fetch_fungible_transfer_history(
+    rpc: &impl SolanaRpc,
     solana_address: &str,
-    token_mint: &str,
-) -> Result<(), Box<dyn Error>> {
-    println!(
-        "\nFetching fungible token {} transaction history for {} via {}\n",
-        token_mint, solana_address, solana_rpc_url
-    );
+    token_mint: Option<&str>,
+    paging: &SignaturePaging,
+    options: &FetchOptions,
+) -> Result<ParsedHistory<FungibleTokenTransfer>> {
+    let mut history = ParsedHistory::default();
 
-    // Create a client
-    let client = Client::new();
-
-    // Fetch the transaction signatures
-    let signatures_payload = serde_json::json!({
-        "jsonrpc": "2.0",
-        "id": 1,
-        "method": "getSignaturesForAddress",
-        "params": [
-            solana_address,
-            {
-                "limit": 10
+    for (signature, json) in
+        fetch_token_history(rpc, solana_address, token_mint, paging, options).await?
+    {
+        let transfers = parse_history_transaction(&signature, &json).and_then(|transaction| {
+            if is_fungible_token_transaction(&transaction) {
+                FungibleTokenTransfer::from_transaction(&transaction, token_mint)
+            } else {
+                Ok(Vec::new())
             }
-        ]
-    });
-
-    let signatures_response = client
-        .post(solana_rpc_url)
-        .json(&signatures_payload)
-        .send()?;
-
-    if !signatures_response.status().is_success() {
-        eprintln!(
-            "Error: Received HTTP {} for transaction signatures request",
-            signatures_response.status()
-        );
-        if let Ok(error_text) = signatures_response.text() {
-            eprintln!("Response: {}", error_text);
+        });
+        match transfers {
+            Ok(transfers) => history
+                .entries
+                .extend(transfers.into_iter().map(|transfer| FungibleTokenTransfer {
+                    memo: transfer.memo.or_else(|| signature.memo.clone()),
+                    ..transfer
+                })),
+            Err(e) => history.skipped.push((signature, e)),
         }
-        std::process::exit(1);
     }
 
-    let response_text = signatures_response.text()?;
-    let signatures_rpc_response: RpcResponse<Vec<SolanaSignature>> =
-        serde_json::from_str(&response_text)?;
+    resolve_owners(rpc, &mut history.entries).await?;
+    Ok(history)
+}
 
-    let mut table = Table::new();
-    table.add_row(row!["Tx ID", "Sender", "Receiver", "Amount", "Timestamp"]);
+/// Returns `owner` followed by its token accounts under both the SPL Token
+/// and Token-2022 programs, only those for `token_mint` if given. Accounts
+/// that have already been closed can't be discovered this way.
+pub async fn token_history_addresses(
+    rpc: &impl SolanaRpc,
+    owner: &str,
+    token_mint: Option<&str>,
+) -> Result<Vec<String>> {
+    let mut addresses = vec![owner.to_string()];
+    for program_id in [spl_token::id(), spl_token_2022::id()] {
+        let accounts = rpc
+            .get_token_accounts_by_owner(owner, &program_id.to_string())
+            .await?;
+        addresses.extend(accounts.iter().filter_map(|keyed| {
+            let mint = keyed.pointer("/account/data/parsed/info/mint")?.as_str()?;
+            let pubkey = keyed.get("pubkey")?.as_str()?;
+            token_mint
+                .is_none_or(|token_mint| mint == token_mint)
+                .then(|| pubkey.to_string())
+        }));
+    }
+    debug!("Fetching token history for {:?}", addresses);
+    Ok(addresses)
+}
 
-    for signature in signatures_rpc_response.result {
-        let transaction_payload = serde_json::json!({
-            "jsonrpc": "2.0",
-            "id": 1,
-            "method": "getTransaction",
-            "params": [
-                signature.signature,
-                "jsonParsed"
-            ]
-        });
+/// Fetches the transactions touching `owner` or any of its token accounts,
+/// newest first. Transfers into an existing token account
+/// paid for by someone else don't list the owner itself.
+async fn fetch_token_history(
+    rpc: &impl SolanaRpc,
+    owner: &str,
+    token_mint: Option<&str>,
+    paging: &SignaturePaging,
+    options: &FetchOptions,
+) -> Result<Vec<(SolanaSignature, Value)>> {
+    let addresses = token_history_addresses(rpc, owner, token_mint).await?;
+    let signatures = fetch_signatures(rpc, &addresses, paging, options).await?;
+    fetch_transactions(rpc, signatures, options).await
+}
 
-        let transaction_response = client
-            .post(solana_rpc_url)
-            .json(&transaction_payload)
-            .send()?;
-        let transaction_response_text = transaction_response.text()?;
-        let json: Value = serde_json::from_str(&transaction_response_text)?;
-
-        if is_fungible_token_transaction(&json) {
-            if let Some(transfer) = FungibleTokenTransfer::from_json(&json, token_mint) {
-                #[allow(deprecated)]
-                let timestamp = Utc.timestamp(transfer.timestamp as i64, 0);
-                table.add_row(Row::new(vec![
-                    Cell::new(&transfer.transaction_id[0..10]),
-                    Cell::new(&transfer.sender),
-                    Cell::new(&transfer.receiver),
-                    Cell::new(&transfer.amount),
-                    Cell::new(&timestamp.to_string()),
-                ]));
+/// Fills in unknown token account owners from the accounts' current state.
+/// Accounts that have since been closed stay unresolved.
+async fn resolve_owners(
+    rpc: &impl SolanaRpc,
+    transfers: &mut [FungibleTokenTransfer],
+) -> Result<()> {
+    let mut unresolved = BTreeSet::new();
+    for transfer in transfers.iter() {
+        if let (Some(sender), None) = (&transfer.sender, &transfer.sender_owner) {
+            unresolved.insert(sender.clone());
+        }
+        if let (Some(receiver), None) = (&transfer.receiver, &transfer.receiver_owner) {
+            if transfer.kind.receiver_is_token_account() {
+                unresolved.insert(receiver.clone());
             }
         }
     }
+    if unresolved.is_empty() {
+        return Ok(());
+    }
+
+    let accounts: Vec<String> = unresolved.into_iter().collect();
+    let owners: HashMap<String, String> = accounts
+        .iter()
+        .zip(rpc.get_multiple_accounts(&accounts).await?)
+        .filter_map(|(account, data)| {
+            let owner = data?
+                .pointer("/data/parsed/info/owner")?
+                .as_str()?
+                .to_string();
+            Some((account.clone(), owner))
+        })
+        .collect();
+    debug!(
+        "Resolved {} of {} token account owners",
+        owners.len(),
+        accounts.len()
+    );
 
-    table.printstd();
+    for transfer in transfers.iter_mut() {
+        if transfer.sender_owner.is_none() {
+            transfer.sender_owner = transfer
+                .sender
+                .as_ref()
+                .and_then(|sender| owners.get(sender).cloned());
+        }
+        if transfer.receiver_owner.is_none() {
+            transfer.receiver_owner = transfer
+                .receiver
+                .as_ref()
+                .and_then(|receiver| owners.get(receiver).cloned());
+        }
+    }
     Ok(())
 }
+
+/// Fetches the net token balance changes of a given Solana address in each
+/// transaction of its and its token accounts' history, newest first, one
+/// entry per token account that changed. Without `token_mint`, changes of
+/// every mint are returned. Transactions that can't be parsed are returned
+/// in `skipped`.
+pub async fn fetch_token_balance_history(
+    rpc: &impl SolanaRpc,
+    solana_address: &str,
+    token_mint: Option<&str>,
+    paging: &SignaturePaging,
+    options: &FetchOptions,
+) -> Result<ParsedHistory<TokenBalanceDelta>> {
+    let mut history = ParsedHistory::default();
+
+    for (signature, json) in
+        fetch_token_history(rpc, solana_address, token_mint, paging, options).await?
+    {
+        let deltas = parse_history_transaction(&signature, &json).and_then(|transaction| {
+            TokenBalanceDelta::from_transaction(&transaction, solana_address, token_mint)
+        });
+        match deltas {
+            Ok(deltas) => {
+                history
+                    .entries
+                    .extend(deltas.into_iter().map(|delta| TokenBalanceDelta {
+                        memo: delta.memo.or_else(|| signature.memo.clone()),
+                        ..delta
+                    }))
+            }
+            Err(e) => history.skipped.push((signature, e)),
+        }
+    }
+
+    Ok(history)
+}
+
+/// Totals of one mint over a token history, in base units.
+#[derive(Debug)]
+pub struct MintSubtotal {
+    pub mint: String,
+    /// Decimal places of the mint, if known.
+    pub decimals: Option<u8>,
+    /// Tokens the wallet received, including mints.
+    pub received: u128,
+    /// Tokens the wallet sent, including burns.
+    pub sent: u128,
+}
+
+impl MintSubtotal {
+    fn new(mint: &str) -> Self {
+        MintSubtotal {
+            mint: mint.to_string(),
+            decimals: None,
+            received: 0,
+            sent: 0,
+        }
+    }
+
+    /// Net change in base units.
+    pub fn net(&self) -> i128 {
+        self.received as i128 - self.sent as i128
+    }
+}
+
+/// Sums the tokens `owner` received and sent per mint, ordered by mint.
+/// A party counts as `owner` if it's the wallet itself or a token account
+/// it owns; approvals and authority changes don't move tokens and are
+/// ignored.
+pub fn transfer_subtotals(transfers: &[FungibleTokenTransfer], owner: &str) -> Vec<MintSubtotal> {
+    let is_owner = |account: &Option<String>, account_owner: &Option<String>| {
+        account.as_deref() == Some(owner) || account_owner.as_deref() == Some(owner)
+    };
+
+    let mut subtotals: BTreeMap<&str, MintSubtotal> = BTreeMap::new();
+    for transfer in transfers {
+        let subtotal = subtotals
+            .entry(&transfer.mint)
+            .or_insert_with(|| MintSubtotal::new(&transfer.mint));
+        subtotal.decimals = subtotal.decimals.or(transfer.decimals);
+
+        let Some(amount) = transfer.amount else {
+            continue;
+        };
+        let (debits, credits) = match transfer.kind {
+            TokenEventKind::Transfer => (true, true),
+            TokenEventKind::MintTo => (false, true),
+            TokenEventKind::Burn => (true, false),
+            _ => (false, false),
+        };
+        if debits && is_owner(&transfer.sender, &transfer.sender_owner) {
+            subtotal.sent += amount as u128;
+        }
+        if credits && is_owner(&transfer.receiver, &transfer.receiver_owner) {
+            subtotal.received += amount as u128;
+        }
+    }
+    subtotals.into_values().collect()
+}
+
+/// Sums balance increases and decreases per mint, ordered by mint.
+pub fn balance_delta_subtotals(deltas: &[TokenBalanceDelta]) -> Vec<MintSubtotal> {
+    let mut subtotals: BTreeMap<&str, MintSubtotal> = BTreeMap::new();
+    for delta in deltas {
+        let subtotal = subtotals
+            .entry(&delta.mint)
+            .or_insert_with(|| MintSubtotal::new(&delta.mint));
+        subtotal.decimals = Some(delta.decimals);
+
+        let change = delta.change();
+        if change > 0 {
+            subtotal.received += change.unsigned_abs();
+        } else {
+            subtotal.sent += change.unsigned_abs();
+        }
+    }
+    subtotals.into_values().collect()
+}
+
+/// Looks up display symbols for `mints`. Only Token-2022 mints carrying the
+/// token metadata extension have one on chain; other mints are left out.
+pub async fn mint_symbols(
+    rpc: &impl SolanaRpc,
+    mints: &[String],
+) -> Result<HashMap<String, String>> {
+    let accounts = rpc.get_multiple_accounts(mints).await?;
+    Ok(mints
+        .iter()
+        .zip(accounts)
+        .filter_map(|(mint, account)| {
+            let symbol = account?
+                .pointer("/data/parsed/info/extensions")?
+                .as_array()?
+                .iter()
+                .find(|extension| {
+                    extension.get("extension").and_then(|v| v.as_str()) == Some("tokenMetadata")
+                })?
+                .pointer("/state/symbol")?
+                .as_str()?
+                .trim()
+                .to_string();
+            (!symbol.is_empty()).then(|| (mint.clone(), symbol))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::MemoryRpc;
+    use crate::test_fixtures::{
+        signature, token_amount, token_balance, token_instruction, transaction, TOKEN_PROGRAM,
+    };
+    use serde_json::json;
+
+    /// Alice sends Bob 1.5 MintA, then Bob sends 0.25 back with a plain
+    /// `transfer` whose token balances don't name Bob's account owner.
+    fn wallet() -> MemoryRpc {
+        let mut rpc = MemoryRpc::new();
+        rpc.add_token_account(
+            "Alice",
+            json!({
+                "pubkey": "AliceAta",
+                "account": {
+                    "owner": TOKEN_PROGRAM,
+                    "data": { "parsed": { "info": { "mint": "MintA", "owner": "Alice" } } }
+                }
+            }),
+        );
+        rpc.add_account(
+            "BobAta",
+            json!({ "data": { "parsed": { "info": { "mint": "MintA", "owner": "Bob" } } } }),
+        );
+        // Alice signed the first transaction, so it's listed under her wallet
+        // as well as her token account.
+        rpc.add_signature("Alice", signature("Sig1", 1, Some(100)));
+        rpc.add_signature("AliceAta", signature("Sig2", 2, Some(200)));
+        rpc.add_signature("AliceAta", signature("Sig1", 1, Some(100)));
+
+        let mut sig1 = transaction(
+            "Sig1",
+            Some(100),
+            &["Alice", "AliceAta", "BobAta", "MintA", TOKEN_PROGRAM],
+            vec![token_instruction(
+                "transferChecked",
+                json!({
+                    "source": "AliceAta",
+                    "destination": "BobAta",
+                    "mint": "MintA",
+                    "authority": "Alice",
+                    "tokenAmount": token_amount(1_500_000, 6)
+                }),
+            )],
+        );
+        sig1["meta"]["preTokenBalances"] = json!([
+            token_balance(1, "MintA", Some("Alice"), 5_000_000, 6),
+            token_balance(2, "MintA", Some("Bob"), 0, 6),
+        ]);
+        sig1["meta"]["postTokenBalances"] = json!([
+            token_balance(1, "MintA", Some("Alice"), 3_500_000, 6),
+            token_balance(2, "MintA", Some("Bob"), 1_500_000, 6),
+        ]);
+        rpc.add_transaction("Sig1", sig1);
+
+        let mut sig2 = transaction(
+            "Sig2",
+            Some(200),
+            &["Bob", "BobAta", "AliceAta", TOKEN_PROGRAM],
+            vec![token_instruction(
+                "transfer",
+                json!({
+                    "source": "BobAta",
+                    "destination": "AliceAta",
+                    "authority": "Bob",
+                    "amount": "250000"
+                }),
+            )],
+        );
+        sig2["meta"]["preTokenBalances"] = json!([
+            token_balance(1, "MintA", None, 1_500_000, 6),
+            token_balance(2, "MintA", Some("Alice"), 3_500_000, 6),
+        ]);
+        sig2["meta"]["postTokenBalances"] = json!([
+            token_balance(1, "MintA", None, 1_250_000, 6),
+            token_balance(2, "MintA", Some("Alice"), 3_750_000, 6),
+        ]);
+        rpc.add_transaction("Sig2", sig2);
+        rpc
+    }
+
+    #[tokio::test]
+    async fn fetches_fungible_transfer_history() {
+        let transfers = fetch_fungible_transfer_history(
+            &wallet(),
+            "Alice",
+            None,
+            &SignaturePaging::default(),
+            &FetchOptions::default(),
+        )
+        .await
+        .unwrap()
+        .entries;
+
+        let summary: Vec<(&str, &str, Option<u64>, Option<u8>)> = transfers
+            .iter()
+            .map(|transfer| {
+                (
+                    transfer.transaction_id.as_str(),
+                    transfer.mint.as_str(),
+                    transfer.amount,
+                    transfer.decimals,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("Sig2", "MintA", Some(250_000), Some(6)),
+                ("Sig1", "MintA", Some(1_500_000), Some(6)),
+            ]
+        );
+        // Bob's owner isn't in the second transaction's token balances and
+        // is looked up from the account instead.
+        assert_eq!(transfers[0].sender_owner.as_deref(), Some("Bob"));
+        assert_eq!(transfers[0].receiver_owner.as_deref(), Some("Alice"));
+    }
+
+    #[tokio::test]
+    async fn fetches_token_balance_history() {
+        let deltas = fetch_token_balance_history(
+            &wallet(),
+            "Alice",
+            Some("MintA"),
+            &SignaturePaging::default(),
+            &FetchOptions::default(),
+        )
+        .await
+        .unwrap()
+        .entries;
+
+        let changes: Vec<(&str, &str, i128)> = deltas
+            .iter()
+            .map(|delta| {
+                (
+                    delta.transaction_id.as_str(),
+                    delta.account.as_str(),
+                    delta.change(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                ("Sig2", "AliceAta", 250_000),
+                ("Sig1", "AliceAta", -1_500_000)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn returns_transactions_that_cannot_be_parsed() {
+        let mut rpc = wallet();
+        rpc.add_transaction("Sig2", json!({ "slot": 2 }));
+
+        let history = fetch_token_balance_history(
+            &rpc,
+            "Alice",
+            None,
+            &SignaturePaging::default(),
+            &FetchOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(history.entries.len(), 1);
+        assert_eq!(history.entries[0].transaction_id, "Sig1");
+        assert_eq!(history.skipped.len(), 1);
+        assert_eq!(history.skipped[0].0.signature, "Sig2");
+    }
+
+    #[tokio::test]
+    async fn resolves_token_account_owners_from_current_state() {
+        let json = transaction(
+            "Sig1",
+            Some(100),
+            &["Alice", "AliceAta", "ClosedAta", "Delegate"],
+            vec![
+                token_instruction(
+                    "transferChecked",
+                    json!({
+                        "source": "AliceAta",
+                        "destination": "ClosedAta",
+                        "mint": "MintA",
+                        "authority": "Alice",
+                        "tokenAmount": token_amount(1, 6)
+                    }),
+                ),
+                token_instruction(
+                    "approveChecked",
+                    json!({
+                        "source": "AliceAta",
+                        "delegate": "Delegate",
+                        "mint": "MintA",
+                        "owner": "Alice",
+                        "tokenAmount": token_amount(1, 6)
+                    }),
+                ),
+            ],
+        );
+        let transaction = crate::transaction::parse_transaction(&json).unwrap();
+        let mut transfers = FungibleTokenTransfer::from_transaction(&transaction, None).unwrap();
+        let mut rpc = MemoryRpc::new();
+        rpc.add_account(
+            "AliceAta",
+            json!({ "data": { "parsed": { "info": { "owner": "Alice" } } } }),
+        );
+        // A delegate isn't a token account and must not be looked up.
+        rpc.add_account(
+            "Delegate",
+            json!({ "data": { "parsed": { "info": { "owner": "Someone" } } } }),
+        );
+
+        resolve_owners(&rpc, &mut transfers).await.unwrap();
+        let owners: Vec<(Option<&str>, Option<&str>)> = transfers
+            .iter()
+            .map(|transfer| {
+                (
+                    transfer.sender_owner.as_deref(),
+                    transfer.receiver_owner.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(owners, [(Some("Alice"), None), (Some("Alice"), None)]);
+    }
+
+    #[tokio::test]
+    async fn token_history_addresses_cover_both_token_programs() {
+        let token_2022 = spl_token_2022::id().to_string();
+        let mut rpc = MemoryRpc::new();
+        for (pubkey, program, mint) in [
+            ("AliceAtaA", TOKEN_PROGRAM, "MintA"),
+            ("AliceAtaB", TOKEN_PROGRAM, "MintB"),
+            ("Alice2022A", token_2022.as_str(), "MintA"),
+        ] {
+            rpc.add_token_account(
+                "Alice",
+                json!({
+                    "pubkey": pubkey,
+                    "account": {
+                        "owner": program,
+                        "data": { "parsed": { "info": { "mint": mint, "owner": "Alice" } } }
+                    }
+                }),
+            );
+        }
+
+        let all = token_history_addresses(&rpc, "Alice", None).await.unwrap();
+        assert_eq!(all, ["Alice", "AliceAtaA", "AliceAtaB", "Alice2022A"]);
+        let mint_a = token_history_addresses(&rpc, "Alice", Some("MintA"))
+            .await
+            .unwrap();
+        assert_eq!(mint_a, ["Alice", "AliceAtaA", "Alice2022A"]);
+    }
+
+    /// A token event moving `amount` base units of a 6-decimal mint, with no
+    /// known owners.
+    fn event(
+        kind: TokenEventKind,
+        mint: &str,
+        sender: Option<&str>,
+        receiver: Option<&str>,
+        amount: u64,
+    ) -> FungibleTokenTransfer {
+        FungibleTokenTransfer {
+            transaction_id: "Sig1".to_string(),
+            kind,
+            mint: mint.to_string(),
+            sender: sender.map(str::to_string),
+            receiver: receiver.map(str::to_string),
+            sender_owner: None,
+            receiver_owner: None,
+            amount: Some(amount),
+            decimals: Some(6),
+            timestamp: 100,
+            instruction_index: 0,
+            stack_height: 1,
+            memo: None,
+        }
+    }
+
+    fn totals(subtotals: &[MintSubtotal]) -> Vec<(&str, Option<u8>, u128, u128)> {
+        subtotals
+            .iter()
+            .map(|subtotal| {
+                (
+                    subtotal.mint.as_str(),
+                    subtotal.decimals,
+                    subtotal.received,
+                    subtotal.sent,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sums_transfers_of_the_wallet_and_its_token_accounts_per_mint() {
+        let mut received = event(
+            TokenEventKind::Transfer,
+            "MintB",
+            Some("BobAta"),
+            Some("AliceAtaB"),
+            300,
+        );
+        received.receiver_owner = Some("Alice".to_string());
+        let mut sent = event(
+            TokenEventKind::Transfer,
+            "MintA",
+            Some("AliceAta"),
+            Some("BobAta"),
+            100,
+        );
+        sent.sender_owner = Some("Alice".to_string());
+        sent.receiver_owner = Some("Bob".to_string());
+        let transfers = [
+            received,
+            sent,
+            // The wallet itself can be the account a mint credits, and its
+            // burns count as sent.
+            event(TokenEventKind::MintTo, "MintA", None, Some("Alice"), 50),
+            event(TokenEventKind::Burn, "MintA", Some("Alice"), None, 20),
+            // Approvals move nothing.
+            event(
+                TokenEventKind::Approve,
+                "MintA",
+                Some("Alice"),
+                Some("Delegate"),
+                1_000,
+            ),
+            // Transfers between other wallets don't count.
+            event(
+                TokenEventKind::Transfer,
+                "MintA",
+                Some("BobAta"),
+                Some("CarolAta"),
+                7,
+            ),
+        ];
+
+        assert_eq!(
+            totals(&transfer_subtotals(&transfers, "Alice")),
+            [("MintA", Some(6), 50, 120), ("MintB", Some(6), 300, 0)]
+        );
+    }
+
+    #[test]
+    fn sums_balance_increases_and_decreases_per_mint() {
+        let delta = |mint: &str, pre_amount: u64, post_amount: u64| TokenBalanceDelta {
+            transaction_id: "Sig1".to_string(),
+            mint: mint.to_string(),
+            account: "AliceAta".to_string(),
+            owner: Some("Alice".to_string()),
+            decimals: 9,
+            pre_amount,
+            post_amount,
+            timestamp: 100,
+            memo: None,
+        };
+        let deltas = [
+            delta("MintB", 0, 40),
+            delta("MintA", 100, 30),
+            delta("MintA", 30, 55),
+        ];
+
+        let subtotals = balance_delta_subtotals(&deltas);
+        assert_eq!(
+            totals(&subtotals),
+            [("MintA", Some(9), 25, 70), ("MintB", Some(9), 40, 0)]
+        );
+        assert_eq!(subtotals[0].net(), -45);
+    }
+}
@@ -1,78 +1,492 @@
-use serde_json::Value;
+use std::fmt;
 
+use crate::amount::format_units;
+use crate::error::Result;
+use crate::memo::transaction_memo;
+use crate::models::{
+    Instruction, ParsedInfo, SolanaTransactionDetails, TokenInstruction, UiTokenAmount,
+};
+use crate::transaction::{instructions, is_failed, timestamp, token_accounts, transaction_id};
+
+/// An SPL token event parsed from a `jsonParsed` transaction: a transfer,
+/// or another token instruction that changes balances or permissions.
 #[derive(Debug)]
 pub struct FungibleTokenTransfer {
     pub transaction_id: String,
-    pub sender: String,
-    pub receiver: String,
-    pub amount: String,
+    /// Token instruction behind the event.
+    pub kind: TokenEventKind,
+    /// Mint of the token involved.
+    pub mint: String,
+    /// Account the event acts on: the debited token account for transfers,
+    /// burns and approvals, the closed account, or the account or mint whose
+    /// authority changes. `None` for mints.
+    pub sender: Option<String>,
+    /// Counterparty: the credited token account for transfers and mints,
+    /// the delegate, the account receiving a closed account's rent, or the
+    /// new authority. `None` where there is none.
+    pub receiver: Option<String>,
+    /// Wallet owning the sender token account, if known.
+    pub sender_owner: Option<String>,
+    /// Wallet owning the receiver token account, if known.
+    pub receiver_owner: Option<String>,
+    /// Amount in base units, for events that move or approve tokens.
+    pub amount: Option<u64>,
+    /// Decimal places of the mint, if known.
+    pub decimals: Option<u8>,
+    /// Block time in unix seconds.
     pub timestamp: u64,
+    /// Index of the top-level instruction behind the event, directly or
+    /// through CPI.
+    pub instruction_index: usize,
+    /// 1 for a top-level instruction, 2 and up for an inner one.
+    pub stack_height: u32,
+    /// Memo of the transaction, if any.
+    pub memo: Option<String>,
 }
 
 impl FungibleTokenTransfer {
-    pub fn from_json(json: &Value, mint_to_match: &str) -> Option<Self> {
-        if let Some(instructions) = json.pointer("/result/transaction/message/instructions") {
-            if let Some(instructions_array) = instructions.as_array() {
-                for instruction in instructions_array {
-                    if let Some(parsed) = instruction.pointer("/parsed/info") {
-                        if let Some(mint) = parsed.pointer("/mint").and_then(|v| v.as_str()) {
-                            if mint == mint_to_match {
-                                if let Some(amount) = parsed
-                                    .pointer("/tokenAmount/uiAmountString")
-                                    .and_then(|v| v.as_str())
-                                {
-                                    if let Some(sender) = parsed
-                                        .pointer("/source")
-                                        .or_else(|| parsed.pointer("/account"))
-                                        .and_then(|v| v.as_str())
-                                    {
-                                        if let Some(receiver) = parsed
-                                            .pointer("/destination")
-                                            .or_else(|| parsed.pointer("/account"))
-                                            .and_then(|v| v.as_str())
-                                        {
-                                            let transaction_id = json
-                                                .pointer("/result/transaction/signatures/0")
-                                                .and_then(|v| v.as_str())?
-                                                .to_string();
-                                            let timestamp = json
-                                                .pointer("/result/blockTime")
-                                                .and_then(|v| v.as_u64())?;
-
-                                            return Some(FungibleTokenTransfer {
-                                                transaction_id,
-                                                sender: sender.to_string(),
-                                                receiver: receiver.to_string(),
-                                                amount: amount.to_string(),
-                                                timestamp,
-                                            });
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
+    /// Parses every token event of a transaction, including inner
+    /// instructions, in execution order. With `mint_to_match`, only events
+    /// of that mint are returned. Owners are taken from the transaction's
+    /// token balances where available. Failed transactions moved no tokens
+    /// and have no events.
+    pub fn from_transaction(
+        transaction: &SolanaTransactionDetails,
+        mint_to_match: Option<&str>,
+    ) -> Result<Vec<Self>> {
+        if is_failed(transaction) {
+            return Ok(Vec::new());
+        }
+        let transaction_id = transaction_id(transaction)?;
+        let timestamp = timestamp(transaction)?;
+        let memo = transaction_memo(transaction);
+
+        let token_accounts = token_accounts(transaction);
+        let balance = |account: Option<&str>| token_accounts.get(account?);
+
+        let mut transfers = Vec::new();
+        for instruction_ref in instructions(transaction) {
+            let Some(token_instruction) = token_instruction(instruction_ref.instruction) else {
+                continue;
+            };
+            let parts = EventParts::from(token_instruction);
+
+            // Only `*Checked` instructions name the mint; otherwise it's
+            // the mint of the token account involved.
+            let Some(mint) = parts.mint.map(String::from).or_else(|| {
+                balance(parts.sender)
+                    .or_else(|| balance(parts.receiver))
+                    .map(|balance| balance.mint.clone())
+            }) else {
+                continue;
+            };
+            if mint_to_match.is_some_and(|mint_to_match| mint != mint_to_match) {
+                continue;
             }
+            let decimals = parts.decimals.or_else(|| {
+                token_accounts
+                    .values()
+                    .find(|balance| balance.mint == mint)
+                    .map(|balance| balance.ui_token_amount.decimals)
+            });
+
+            transfers.push(FungibleTokenTransfer {
+                transaction_id: transaction_id.clone(),
+                kind: parts.kind,
+                sender: parts.sender.map(String::from),
+                receiver: parts.receiver.map(String::from),
+                sender_owner: balance(parts.sender).and_then(|balance| balance.owner.clone()),
+                receiver_owner: balance(parts.receiver).and_then(|balance| balance.owner.clone()),
+                mint,
+                amount: parts.amount,
+                decimals,
+                timestamp,
+                instruction_index: instruction_ref.index,
+                stack_height: instruction_ref.stack_height,
+                memo: memo.clone(),
+            });
         }
-        None
+        Ok(transfers)
+    }
+
+    /// The amount in UI units, or `None` if the event has no amount or the
+    /// mint's decimals are unknown.
+    pub fn ui_amount(&self) -> Option<String> {
+        Some(format_units(self.amount? as i128, self.decimals?))
     }
 }
 
-pub fn is_fungible_token_transaction(json: &Value) -> bool {
-    if let Some(instructions) = json.pointer("/result/transaction/message/instructions") {
-        if let Some(instructions_array) = instructions.as_array() {
-            for instruction in instructions_array {
-                if let Some(parsed) = instruction.pointer("/parsed/info") {
-                    let mint = parsed.pointer("/mint").and_then(|v| v.as_str());
-                    let token_amount = parsed.pointer("/tokenAmount");
-
-                    if mint.is_some() && token_amount.is_some() {
-                        return true;
-                    }
-                }
+/// Token instructions reported as events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenEventKind {
+    Transfer,
+    MintTo,
+    Burn,
+    /// Closes a token account, sending its rent lamports elsewhere.
+    CloseAccount,
+    /// Lets a delegate spend tokens from an account.
+    Approve,
+    /// Removes an account's delegate.
+    Revoke,
+    /// Changes an authority of an account or mint.
+    SetAuthority,
+}
+
+impl TokenEventKind {
+    /// Whether the receiver is a token account rather than a wallet or
+    /// authority.
+    pub fn receiver_is_token_account(self) -> bool {
+        matches!(self, TokenEventKind::Transfer | TokenEventKind::MintTo)
+    }
+}
+
+impl fmt::Display for TokenEventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TokenEventKind::Transfer => "transfer",
+            TokenEventKind::MintTo => "mintTo",
+            TokenEventKind::Burn => "burn",
+            TokenEventKind::CloseAccount => "closeAccount",
+            TokenEventKind::Approve => "approve",
+            TokenEventKind::Revoke => "revoke",
+            TokenEventKind::SetAuthority => "setAuthority",
+        };
+        f.write_str(name)
+    }
+}
+
+/// One token instruction mapped onto event parties. `mint` and `decimals`
+/// are only known here for `*Checked` instructions and instructions acting
+/// on a mint.
+struct EventParts<'a> {
+    kind: TokenEventKind,
+    sender: Option<&'a str>,
+    receiver: Option<&'a str>,
+    mint: Option<&'a str>,
+    amount: Option<u64>,
+    decimals: Option<u8>,
+}
+
+impl<'a> From<&'a TokenInstruction> for EventParts<'a> {
+    fn from(instruction: &'a TokenInstruction) -> Self {
+        let parts = |kind, sender: Option<&'a String>, receiver: Option<&'a String>| EventParts {
+            kind,
+            sender: sender.map(String::as_str),
+            receiver: receiver.map(String::as_str),
+            mint: None,
+            amount: None,
+            decimals: None,
+        };
+        let checked =
+            |parts: EventParts<'a>, mint: &'a String, token_amount: &'a UiTokenAmount| EventParts {
+                mint: Some(mint),
+                amount: token_amount.raw_amount(),
+                decimals: Some(token_amount.decimals),
+                ..parts
+            };
+
+        match instruction {
+            TokenInstruction::Transfer {
+                source,
+                destination,
+                amount,
+            } => EventParts {
+                amount: Some(*amount),
+                ..parts(TokenEventKind::Transfer, Some(source), Some(destination))
+            },
+            TokenInstruction::TransferChecked {
+                source,
+                destination,
+                mint,
+                token_amount,
+            } => checked(
+                parts(TokenEventKind::Transfer, Some(source), Some(destination)),
+                mint,
+                token_amount,
+            ),
+            TokenInstruction::MintTo {
+                mint,
+                account,
+                amount,
+            } => EventParts {
+                mint: Some(mint),
+                amount: Some(*amount),
+                ..parts(TokenEventKind::MintTo, None, Some(account))
+            },
+            TokenInstruction::MintToChecked {
+                mint,
+                account,
+                token_amount,
+            } => checked(
+                parts(TokenEventKind::MintTo, None, Some(account)),
+                mint,
+                token_amount,
+            ),
+            TokenInstruction::Burn {
+                account,
+                mint,
+                amount,
+            } => EventParts {
+                mint: Some(mint),
+                amount: Some(*amount),
+                ..parts(TokenEventKind::Burn, Some(account), None)
+            },
+            TokenInstruction::BurnChecked {
+                account,
+                mint,
+                token_amount,
+            } => checked(
+                parts(TokenEventKind::Burn, Some(account), None),
+                mint,
+                token_amount,
+            ),
+            TokenInstruction::CloseAccount {
+                account,
+                destination,
+            } => parts(
+                TokenEventKind::CloseAccount,
+                Some(account),
+                Some(destination),
+            ),
+            TokenInstruction::Approve {
+                source,
+                delegate,
+                amount,
+            } => EventParts {
+                amount: Some(*amount),
+                ..parts(TokenEventKind::Approve, Some(source), Some(delegate))
+            },
+            TokenInstruction::ApproveChecked {
+                source,
+                delegate,
+                mint,
+                token_amount,
+            } => checked(
+                parts(TokenEventKind::Approve, Some(source), Some(delegate)),
+                mint,
+                token_amount,
+            ),
+            TokenInstruction::Revoke { source } => {
+                parts(TokenEventKind::Revoke, Some(source), None)
             }
+            TokenInstruction::SetAuthority {
+                account,
+                mint,
+                new_authority,
+                ..
+            } => EventParts {
+                mint: mint.as_deref(),
+                ..parts(
+                    TokenEventKind::SetAuthority,
+                    account.as_ref().or(mint.as_ref()),
+                    new_authority.as_ref(),
+                )
+            },
         }
     }
-    false
+}
+
+/// Returns the SPL Token or Token-2022 instruction, if `instruction` is one
+/// this crate models.
+fn token_instruction(instruction: &Instruction) -> Option<&TokenInstruction> {
+    match instruction {
+        Instruction::Parsed(parsed) => match &parsed.parsed {
+            ParsedInfo::Token(token_instruction) => Some(token_instruction),
+            _ => None,
+        },
+        Instruction::PartiallyDecoded(_) => None,
+    }
+}
+
+/// Returns true if any instruction of a transaction, top-level or inner, is
+/// a token event.
+pub fn is_fungible_token_transaction(transaction: &SolanaTransactionDetails) -> bool {
+    instructions(transaction)
+        .iter()
+        .any(|instruction_ref| token_instruction(instruction_ref.instruction).is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{token_amount, token_balance, token_instruction, transaction};
+    use crate::transaction::parse_transaction;
+    use serde_json::{json, Value};
+
+    fn parse(json: &Value, mint: Option<&str>) -> Vec<FungibleTokenTransfer> {
+        FungibleTokenTransfer::from_transaction(&parse_transaction(json).unwrap(), mint).unwrap()
+    }
+
+    fn transfer_checked(source: &str, destination: &str, mint: &str, amount: u64) -> Value {
+        token_instruction(
+            "transferChecked",
+            json!({
+                "source": source,
+                "destination": destination,
+                "mint": mint,
+                "authority": "Alice",
+                "tokenAmount": token_amount(amount, 6)
+            }),
+        )
+    }
+
+    #[test]
+    fn includes_inner_token_transfers() {
+        let mut json = transaction(
+            "Sig1",
+            Some(100),
+            &["Alice", "AliceAta", "PoolAta", "Program1111"],
+            vec![
+                json!({ "programId": "Program1111", "accounts": [], "data": "", "stackHeight": null }),
+            ],
+        );
+        let mut inner = transfer_checked("PoolAta", "AliceAta", "MintA", 42);
+        inner["stackHeight"] = json!(2);
+        json["meta"]["innerInstructions"] = json!([{ "index": 0, "instructions": [inner] }]);
+
+        let transfers = parse(&json, None);
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].sender.as_deref(), Some("PoolAta"));
+        assert_eq!(transfers[0].amount, Some(42));
+        assert_eq!(transfers[0].instruction_index, 0);
+        assert_eq!(transfers[0].stack_height, 2);
+    }
+
+    #[test]
+    fn infers_mint_and_decimals_of_plain_transfers() {
+        let mut json = transaction(
+            "Sig1",
+            Some(100),
+            &["Alice", "AliceAta", "BobAta", "Unknown1", "Unknown2"],
+            vec![
+                token_instruction(
+                    "transfer",
+                    json!({ "source": "AliceAta", "destination": "BobAta", "authority": "Alice", "amount": "250" }),
+                ),
+                // Neither account has a token balance, so the mint is unknown.
+                token_instruction(
+                    "transfer",
+                    json!({ "source": "Unknown1", "destination": "Unknown2", "authority": "Alice", "amount": "1" }),
+                ),
+            ],
+        );
+        // Only the destination has a balance entry; its mint applies to both.
+        json["meta"]["postTokenBalances"] = json!([token_balance(2, "MintA", Some("Bob"), 250, 2)]);
+
+        let transfers = parse(&json, None);
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].mint, "MintA");
+        assert_eq!(transfers[0].decimals, Some(2));
+        assert_eq!(transfers[0].ui_amount().as_deref(), Some("2.5"));
+        assert_eq!(transfers[0].receiver_owner.as_deref(), Some("Bob"));
+        assert_eq!(transfers[0].sender_owner, None);
+    }
+
+    #[test]
+    fn returns_every_matching_transfer() {
+        let json = transaction(
+            "Sig1",
+            Some(100),
+            &["Alice", "AliceAta", "BobAta", "CarolAta", "AliceB", "BobB"],
+            vec![
+                transfer_checked("AliceAta", "BobAta", "MintA", 10),
+                transfer_checked("AliceB", "BobB", "MintB", 20),
+                transfer_checked("AliceAta", "CarolAta", "MintA", 30),
+            ],
+        );
+
+        let amounts = |mint: Option<&str>| -> Vec<(String, Option<u64>)> {
+            parse(&json, mint)
+                .into_iter()
+                .map(|transfer| (transfer.mint, transfer.amount))
+                .collect()
+        };
+        assert_eq!(
+            amounts(Some("MintA")),
+            [
+                ("MintA".to_string(), Some(10)),
+                ("MintA".to_string(), Some(30))
+            ]
+        );
+        assert_eq!(amounts(None).len(), 3);
+        assert!(amounts(Some("MintC")).is_empty());
+    }
+
+    #[test]
+    fn classifies_token_events_with_their_parties() {
+        let mut json = transaction(
+            "Sig1",
+            Some(100),
+            &["Alice", "AliceAta", "MintA", "OldAta", "Delegate", "Carol"],
+            vec![
+                token_instruction(
+                    "mintTo",
+                    json!({ "mint": "MintA", "account": "AliceAta", "mintAuthority": "Carol", "amount": "100" }),
+                ),
+                token_instruction(
+                    "burnChecked",
+                    json!({ "account": "AliceAta", "mint": "MintA", "authority": "Alice", "tokenAmount": token_amount(40, 6) }),
+                ),
+                token_instruction(
+                    "approve",
+                    json!({ "source": "AliceAta", "delegate": "Delegate", "owner": "Alice", "amount": "5" }),
+                ),
+                token_instruction("revoke", json!({ "source": "AliceAta", "owner": "Alice" })),
+                token_instruction(
+                    "closeAccount",
+                    json!({ "account": "OldAta", "destination": "Alice", "owner": "Alice" }),
+                ),
+                token_instruction(
+                    "setAuthority",
+                    json!({ "mint": "MintA", "authority": "Carol", "authorityType": "mintTokens", "newAuthority": "Alice" }),
+                ),
+            ],
+        );
+        json["meta"]["postTokenBalances"] = json!([
+            token_balance(1, "MintA", Some("Alice"), 60, 6),
+            token_balance(3, "MintA", Some("Alice"), 0, 6),
+        ]);
+
+        let transfers = parse(&json, Some("MintA"));
+        let events: Vec<_> = transfers
+            .iter()
+            .map(|transfer| {
+                (
+                    transfer.kind,
+                    transfer.sender.as_deref(),
+                    transfer.receiver.as_deref(),
+                    transfer.amount,
+                )
+            })
+            .collect();
+        assert_eq!(
+            events,
+            [
+                (TokenEventKind::MintTo, None, Some("AliceAta"), Some(100)),
+                (TokenEventKind::Burn, Some("AliceAta"), None, Some(40)),
+                (
+                    TokenEventKind::Approve,
+                    Some("AliceAta"),
+                    Some("Delegate"),
+                    Some(5)
+                ),
+                (TokenEventKind::Revoke, Some("AliceAta"), None, None),
+                (
+                    TokenEventKind::CloseAccount,
+                    Some("OldAta"),
+                    Some("Alice"),
+                    None
+                ),
+                (
+                    TokenEventKind::SetAuthority,
+                    Some("MintA"),
+                    Some("Alice"),
+                    None
+                ),
+            ]
+        );
+        assert!(transfers
+            .iter()
+            .all(|transfer| transfer.decimals == Some(6)));
+    }
 }
@@ -1,85 +1,545 @@
-use log::debug;
-use reqwest::blocking::Client;
-use serde_json;
-use std::error::Error;
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
+use log::{debug, warn};
+use serde_json::Value;
+use std::cmp::Reverse;
+use std::collections::HashSet;
 use std::fs::File;
 use std::io::Write;
+use std::path::{Path, PathBuf};
 
-use crate::models::{RpcResponse, SolanaSignature};
+use crate::error::{Error, Result};
+use crate::models::{SolanaSignature, SolanaTransactionDetails};
+use crate::rpc::SolanaRpc;
+use crate::transaction::parse_transaction;
 
-pub fn fetch_transaction_history(
-    solana_rpc_url: &str,
-    solana_address: &str,
-) -> Result<(), Box<dyn Error>> {
-    // Create a client
-    let client = Client::new();
-
-    // Fetch the transaction signatures
-    let signatures_payload = serde_json::json!({
-        "jsonrpc": "2.0",
-        "id": 1,
-        "method": "getSignaturesForAddress",
-        "params": [
+/// Largest page `getSignaturesForAddress` will return in a single call.
+const MAX_SIGNATURES_PER_PAGE: usize = 1000;
+
+/// Cursor options used to walk an address's signatures backwards in time.
+#[derive(Debug, Default, Clone)]
+pub struct SignaturePaging {
+    /// Maximum number of signatures to return; `None` walks the full history.
+    pub limit: Option<usize>,
+    /// Start searching backwards from this signature (exclusive).
+    pub before: Option<String>,
+    /// Stop once this signature is reached (exclusive).
+    pub until: Option<String>,
+    /// Stop paging once block times fall below this unix timestamp (inclusive bound).
+    pub since: Option<i64>,
+    /// Skip signatures with block times newer than this unix timestamp (inclusive bound).
+    pub until_time: Option<i64>,
+}
+
+/// Controls how transaction bodies are fetched once signatures are known.
+#[derive(Debug, Clone)]
+pub struct FetchOptions {
+    /// Number of `getTransaction` calls grouped into one JSON-RPC batch.
+    pub batch_size: usize,
+    /// Maximum number of requests in flight at once.
+    pub concurrency: usize,
+}
+
+impl Default for FetchOptions {
+    fn default() -> Self {
+        FetchOptions {
+            batch_size: 50,
+            concurrency: 4,
+        }
+    }
+}
+
+/// Entries parsed from a transaction history, together with the
+/// transactions that couldn't be parsed, so callers can decide whether to
+/// retry, report or abort.
+#[derive(Debug)]
+pub struct ParsedHistory<T> {
+    pub entries: Vec<T>,
+    /// Transactions left out of `entries`, with the reason each couldn't be
+    /// parsed, newest first.
+    pub skipped: Vec<(SolanaSignature, Error)>,
+}
+
+impl<T> Default for ParsedHistory<T> {
+    fn default() -> Self {
+        ParsedHistory {
+            entries: Vec::new(),
+            skipped: Vec::new(),
+        }
+    }
+}
+
+impl SignaturePaging {
+    fn has_time_bounds(&self) -> bool {
+        self.since.is_some() || self.until_time.is_some()
+    }
+}
+
+/// Position of a signature walk between pages.
+struct PageCursor {
+    before: Option<String>,
+    fetched: usize,
+    done: bool,
+}
+
+/// Streams signatures for `solana_address` page by page, newest first,
+/// following the `before` cursor until `paging.until`, `paging.limit`,
+/// `paging.since` or the start of the account's history is reached.
+///
+/// When time bounds are set, signatures without a `blockTime` are resolved
+/// with `getBlockTime` for their slot. Signatures whose time still cannot be
+/// determined are kept, but never used to stop paging.
+pub fn signature_pages<'a>(
+    rpc: &'a impl SolanaRpc,
+    solana_address: &'a str,
+    paging: &'a SignaturePaging,
+) -> impl Stream<Item = Result<Vec<SolanaSignature>>> + 'a {
+    let cursor = PageCursor {
+        before: paging.before.clone(),
+        fetched: 0,
+        done: false,
+    };
+
+    stream::try_unfold(cursor, move |mut cursor| async move {
+        // Signatures newer than `until_time` are skipped, so a page sized to
+        // the remaining limit could be used up by them alone.
+        let page_size = match paging.limit {
+            Some(limit) if paging.until_time.is_none() => {
+                MAX_SIGNATURES_PER_PAGE.min(limit - cursor.fetched)
+            }
+            _ => MAX_SIGNATURES_PER_PAGE,
+        };
+        if cursor.done || paging.limit.is_some_and(|limit| cursor.fetched >= limit) {
+            return Ok(None);
+        }
+
+        let page = rpc
+            .get_signatures(
+                solana_address,
+                cursor.before.as_deref(),
+                paging.until.as_deref(),
+                page_size,
+            )
+            .await?;
+
+        debug!(
+            "Fetched {} signatures for {} before {:?}",
+            page.len(),
             solana_address,
+            cursor.before
+        );
+
+        // A short page means we hit `until` or the start of the history.
+        let exhausted = page.len() < page_size;
+        cursor.before = page.last().map(|signature| signature.signature.clone());
+
+        let mut signatures = Vec::with_capacity(page.len());
+        let mut reached_since = false;
+        for mut signature in page {
+            if paging.has_time_bounds() && signature.block_time.is_none() {
+                signature.block_time = rpc.get_block_time(signature.slot).await?;
+                if signature.block_time.is_none() {
+                    warn!(
+                        "No block time available for signature {}, keeping it",
+                        signature.signature
+                    );
+                }
+            }
+
+            if let Some(block_time) = signature.block_time {
+                if paging.since.is_some_and(|since| block_time < since) {
+                    reached_since = true;
+                    break;
+                }
+                if paging.until_time.is_some_and(|until| block_time > until) {
+                    continue;
+                }
+            }
+
+            signatures.push(signature);
+            if paging
+                .limit
+                .is_some_and(|limit| cursor.fetched + signatures.len() >= limit)
             {
-                "limit": 10
+                break;
             }
-        ]
-    });
-
-    let signatures_response = client
-        .post(solana_rpc_url)
-        .json(&signatures_payload)
-        .send()?;
-
-    if !signatures_response.status().is_success() {
-        eprintln!(
-            "Error: Received HTTP {} for transaction signatures request",
-            signatures_response.status()
-        );
-        if let Ok(error_text) = signatures_response.text() {
-            eprintln!("Response: {}", error_text);
         }
-        std::process::exit(1);
-    }
-
-    let response_text = signatures_response.text()?;
-    let signatures_rpc_response: RpcResponse<Vec<SolanaSignature>> =
-        serde_json::from_str(&response_text)?;
-
-    for signature in signatures_rpc_response.result {
-        if let Some(_signature_block_time) = signature.block_time {
-            let transaction_payload = serde_json::json!({
-                "jsonrpc": "2.0",
-                "id": 1,
-                "method": "getTransaction",
-                "params": [
-                    signature.signature,
-                    "jsonParsed"
-                ]
-            });
-
-            let transaction_response = client
-                .post(solana_rpc_url)
-                .json(&transaction_payload)
-                .send()?;
-
-            let transaction_response_text = transaction_response.text()?;
-
-            // Assuming `signature.signature` is the transaction ID
-            // Use only the first 10 characters of the signature for the filename
-            let file_name = format!("serializations/{}.json", &signature.signature[0..10]);
-            let mut file = File::create(&file_name)?;
-            file.write_all(transaction_response_text.as_bytes())?;
-
-            debug!("Transaction data written to file: {}", file_name);
-        } else {
-            debug!(
-                "No block time available for signature: {}",
-                signature.signature
-            );
+
+        cursor.fetched += signatures.len();
+        cursor.done = exhausted || reached_since || cursor.before.is_none();
+        Ok(Some((signatures, cursor)))
+    })
+}
+
+/// Parses a transaction fetched for `signature`. Nodes that report no
+/// `blockTime` for the transaction get the signature's, which
+/// `signature_pages` may have resolved with `getBlockTime`.
+pub fn parse_history_transaction(
+    signature: &SolanaSignature,
+    json: &Value,
+) -> Result<SolanaTransactionDetails> {
+    let mut transaction = parse_transaction(json)?;
+    transaction.block_time = transaction.block_time.or(signature.block_time);
+    Ok(transaction)
+}
+
+/// Fetches the transactions for one batch of signatures, dropping signatures
+/// the node no longer has.
+async fn fetch_batch(
+    rpc: &impl SolanaRpc,
+    batch: Vec<SolanaSignature>,
+) -> Result<Vec<(SolanaSignature, Value)>> {
+    let ids: Vec<String> = batch.iter().map(|s| s.signature.clone()).collect();
+    let transactions = rpc.get_transactions(&ids).await?;
+
+    Ok(batch
+        .into_iter()
+        .zip(transactions)
+        .filter_map(|(signature, transaction)| match transaction {
+            Some(transaction) => Some((signature, transaction)),
+            None => {
+                warn!("Transaction not found: {}", signature.signature);
+                None
+            }
+        })
+        .collect())
+}
+
+/// Fetches signatures and their transactions in one pipeline. Transactions
+/// are requested `options.batch_size` per call with up to
+/// `options.concurrency` calls in flight, while the next signature page is
+/// still loading. Results are in signature order, newest slot first.
+pub async fn fetch_history(
+    rpc: &impl SolanaRpc,
+    solana_address: &str,
+    paging: &SignaturePaging,
+    options: &FetchOptions,
+) -> Result<Vec<(SolanaSignature, Value)>> {
+    let batch_size = options.batch_size.max(1);
+
+    signature_pages(rpc, solana_address, paging)
+        .map_ok(|page| {
+            let batches: Vec<Vec<SolanaSignature>> = page
+                .chunks(batch_size)
+                .map(|batch| batch.to_vec())
+                .collect();
+            stream::iter(batches).map(Ok)
+        })
+        .try_flatten()
+        .map_ok(|batch| fetch_batch(rpc, batch))
+        .try_buffered(options.concurrency.max(1))
+        .try_concat()
+        .await
+}
+
+/// Fetches the signatures of several addresses and merges them into one
+/// history, newest slot first, with each signature listed once. `paging`
+/// applies to every address; `paging.limit` also caps the merged result.
+pub async fn fetch_signatures(
+    rpc: &impl SolanaRpc,
+    addresses: &[String],
+    paging: &SignaturePaging,
+    options: &FetchOptions,
+) -> Result<Vec<SolanaSignature>> {
+    let per_address: Vec<Vec<SolanaSignature>> = stream::iter(addresses)
+        .map(|address| signature_pages(rpc, address, paging).try_concat())
+        .buffered(options.concurrency.max(1))
+        .try_collect()
+        .await?;
+
+    let mut seen = HashSet::new();
+    let mut signatures: Vec<SolanaSignature> = per_address
+        .into_iter()
+        .flatten()
+        .filter(|signature| seen.insert(signature.signature.clone()))
+        .collect();
+    signatures.sort_by_key(|signature| Reverse(signature.slot));
+    if let Some(limit) = paging.limit {
+        signatures.truncate(limit);
+    }
+
+    debug!(
+        "Merged {} signatures for {} addresses",
+        signatures.len(),
+        addresses.len()
+    );
+    Ok(signatures)
+}
+
+/// Fetches the transactions for `signatures`, `options.batch_size` per call
+/// with up to `options.concurrency` calls in flight. Results keep the order
+/// of `signatures`.
+pub async fn fetch_transactions(
+    rpc: &impl SolanaRpc,
+    signatures: Vec<SolanaSignature>,
+    options: &FetchOptions,
+) -> Result<Vec<(SolanaSignature, Value)>> {
+    let batches: Vec<Vec<SolanaSignature>> = signatures
+        .chunks(options.batch_size.max(1))
+        .map(|batch| batch.to_vec())
+        .collect();
+
+    stream::iter(batches)
+        .map(|batch| fetch_batch(rpc, batch))
+        .buffered(options.concurrency.max(1))
+        .try_concat()
+        .await
+}
+
+/// Fetches transaction history and saves each transaction to
+/// `<directory>/<first 10 characters of the signature>.json`, wrapped in a
+/// JSON-RPC response envelope as `{"jsonrpc", "result", "id"}`. Returns the
+/// paths written, newest transaction first.
+pub async fn save_transaction_history(
+    rpc: &impl SolanaRpc,
+    solana_address: &str,
+    paging: &SignaturePaging,
+    options: &FetchOptions,
+    directory: &Path,
+) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for (signature, transaction) in fetch_history(rpc, solana_address, paging, options).await? {
+        let file_name = directory.join(format!("{}.json", &signature.signature[0..10]));
+        let mut file = File::create(&file_name)?;
+        let response = serde_json::json!({
+            "jsonrpc": "2.0",
+            "result": transaction,
+            "id": 1
+        });
+        file.write_all(serde_json::to_string(&response)?.as_bytes())?;
+
+        debug!("Transaction data written to file: {}", file_name.display());
+        paths.push(file_name);
+    }
+
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::MemoryRpc;
+    use crate::test_fixtures;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn signature(slot: u64, block_time: Option<i64>) -> SolanaSignature {
+        serde_json::from_value(serde_json::json!({
+            "signature": format!("sig{}", slot),
+            "slot": slot,
+            "blockTime": block_time,
+            "confirmationStatus": "finalized",
+            "err": null,
+            "memo": null
+        }))
+        .unwrap()
+    }
+
+    /// A history of `count` signatures at slots `count` down to 1, each with
+    /// a block time of ten times its slot.
+    fn history(count: u64) -> MemoryRpc {
+        let mut rpc = MemoryRpc::new();
+        for slot in (1..=count).rev() {
+            rpc.add_signature("addr", signature(slot, Some(slot as i64 * 10)));
+        }
+        rpc
+    }
+
+    fn slots(signatures: &[SolanaSignature]) -> Vec<u64> {
+        signatures.iter().map(|signature| signature.slot).collect()
+    }
+
+    async fn collect(rpc: &impl SolanaRpc, paging: &SignaturePaging) -> Vec<SolanaSignature> {
+        signature_pages(rpc, "addr", paging)
+            .try_concat()
+            .await
+            .unwrap()
+    }
+
+    /// Counts `getSignaturesForAddress` calls made against a `MemoryRpc`.
+    struct CountingRpc {
+        inner: MemoryRpc,
+        signature_calls: AtomicUsize,
+    }
+
+    impl SolanaRpc for CountingRpc {
+        async fn get_signatures(
+            &self,
+            address: &str,
+            before: Option<&str>,
+            until: Option<&str>,
+            limit: usize,
+        ) -> Result<Vec<SolanaSignature>> {
+            self.signature_calls.fetch_add(1, Ordering::Relaxed);
+            self.inner
+                .get_signatures(address, before, until, limit)
+                .await
+        }
+
+        async fn get_transaction(&self, signature: &str) -> Result<Option<Value>> {
+            self.inner.get_transaction(signature).await
+        }
+
+        async fn get_block_time(&self, slot: u64) -> Result<Option<i64>> {
+            self.inner.get_block_time(slot).await
+        }
+
+        async fn get_account(&self, pubkey: &str) -> Result<Option<Value>> {
+            self.inner.get_account(pubkey).await
+        }
+
+        async fn get_token_accounts_by_owner(
+            &self,
+            owner: &str,
+            program_id: &str,
+        ) -> Result<Vec<Value>> {
+            self.inner
+                .get_token_accounts_by_owner(owner, program_id)
+                .await
+        }
+    }
+
+    #[tokio::test]
+    async fn walks_full_history_across_pages() {
+        let rpc = history(2500);
+        let signatures = collect(&rpc, &SignaturePaging::default()).await;
+        assert_eq!(slots(&signatures), (1..=2500).rev().collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn limit_spans_page_boundaries() {
+        let rpc = history(2500);
+        let paging = SignaturePaging {
+            limit: Some(1500),
+            ..SignaturePaging::default()
+        };
+        let signatures = collect(&rpc, &paging).await;
+        assert_eq!(slots(&signatures), (1001..=2500).rev().collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn before_and_until_are_exclusive() {
+        let rpc = history(2500);
+        let paging = SignaturePaging {
+            before: Some("sig2000".to_string()),
+            until: Some("sig1990".to_string()),
+            ..SignaturePaging::default()
+        };
+        let signatures = collect(&rpc, &paging).await;
+        assert_eq!(slots(&signatures), (1991..=1999).rev().collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn since_stops_paging() {
+        let rpc = CountingRpc {
+            inner: history(2500),
+            signature_calls: AtomicUsize::new(0),
+        };
+        let paging = SignaturePaging {
+            since: Some(24_000),
+            until_time: Some(24_990),
+            ..SignaturePaging::default()
+        };
+        let signatures = collect(&rpc, &paging).await;
+        assert_eq!(slots(&signatures), (2400..=2499).rev().collect::<Vec<_>>());
+        assert_eq!(rpc.signature_calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn until_time_requests_full_pages_despite_a_small_limit() {
+        let rpc = CountingRpc {
+            inner: history(5000),
+            signature_calls: AtomicUsize::new(0),
+        };
+        let paging = SignaturePaging {
+            limit: Some(10),
+            until_time: Some(10_000),
+            ..SignaturePaging::default()
+        };
+        let signatures = collect(&rpc, &paging).await;
+        assert_eq!(slots(&signatures), (991..=1000).rev().collect::<Vec<_>>());
+        assert_eq!(rpc.signature_calls.load(Ordering::Relaxed), 5);
+    }
+
+    #[tokio::test]
+    async fn resolves_missing_block_times_with_get_block_time() {
+        let mut rpc = MemoryRpc::new();
+        for slot in (1..=5).rev() {
+            rpc.add_signature("addr", signature(slot, None));
+        }
+        for slot in 2..=5 {
+            rpc.add_block_time(slot, slot as i64 * 10);
         }
+        let paging = SignaturePaging {
+            since: Some(30),
+            ..SignaturePaging::default()
+        };
+        let signatures = collect(&rpc, &paging).await;
+        assert_eq!(slots(&signatures), vec![5, 4, 3]);
+        assert_eq!(signatures[0].block_time, Some(50));
+
+        // Without a block time a signature is kept and doesn't stop paging.
+        let mut rpc = MemoryRpc::new();
+        rpc.add_signature("addr", signature(2, None));
+        rpc.add_signature("addr", signature(1, Some(0)));
+        let signatures = collect(&rpc, &paging).await;
+        assert_eq!(slots(&signatures), vec![2]);
+        assert_eq!(signatures[0].block_time, None);
     }
 
-    Ok(())
+    #[tokio::test]
+    async fn fetch_history_skips_missing_transactions() {
+        let mut rpc = history(5);
+        for slot in [5, 4, 2, 1] {
+            rpc.add_transaction(&format!("sig{}", slot), serde_json::json!({ "slot": slot }));
+        }
+        let options = FetchOptions {
+            batch_size: 2,
+            concurrency: 2,
+        };
+        let history = fetch_history(&rpc, "addr", &SignaturePaging::default(), &options)
+            .await
+            .unwrap();
+        let fetched: Vec<u64> = history
+            .iter()
+            .map(|(signature, transaction)| {
+                assert_eq!(transaction["slot"], signature.slot);
+                signature.slot
+            })
+            .collect();
+        assert_eq!(fetched, vec![5, 4, 2, 1]);
+    }
+
+    #[tokio::test]
+    async fn saves_transactions_in_a_response_envelope() {
+        let mut rpc = MemoryRpc::new();
+        for slot in [2, 1] {
+            let id = format!("Signature{}", slot);
+            rpc.add_signature("addr", test_fixtures::signature(&id, slot, None));
+            rpc.add_transaction(&id, serde_json::json!({ "slot": slot }));
+        }
+        let directory = std::env::temp_dir().join(format!("solutil-save-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let paths = save_transaction_history(
+            &rpc,
+            "addr",
+            &SignaturePaging::default(),
+            &FetchOptions::default(),
+            &directory,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            paths,
+            [
+                directory.join("Signature2.json"),
+                directory.join("Signature1.json")
+            ]
+        );
+        let saved: Value =
+            serde_json::from_str(&std::fs::read_to_string(&paths[0]).unwrap()).unwrap();
+        assert_eq!(saved["jsonrpc"], "2.0");
+        assert_eq!(saved.pointer("/result/slot"), Some(&serde_json::json!(2)));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
 }
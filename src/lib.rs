@@ -0,0 +1,63 @@
+//! Solana wallet utilities: transaction history, transfer parsing and
+//! sending SOL or SPL tokens.
+//!
+//! History functions take any [`SolanaRpc`] implementation, so they run
+//! against a live node through [`HttpRpc`] or against fixtures through
+//! [`MemoryRpc`]. They return data and leave presentation to the caller.
+//!
+//! ```no_run
+//! use solana_sdk::commitment_config::CommitmentConfig;
+//! use solutil::{sol_history, FetchOptions, HttpRpc, RetryPolicy, SignaturePaging};
+//!
+//! # async fn example() -> solutil::Result<()> {
+//! let rpc = HttpRpc::new(
+//!     "https://api.devnet.solana.com",
+//!     RetryPolicy::default(),
+//!     CommitmentConfig::finalized(),
+//! );
+//! let paging = SignaturePaging {
+//!     limit: Some(100),
+//!     ..SignaturePaging::default()
+//! };
+//! let transfers = sol_history::fetch_sol_transfer_history(
+//!     &rpc,
+//!     "4S4ZvPYTuPx2A2neQJyeoW5PqU2nrdd5VdxLc6CkPywV",
+//!     &paging,
+//!     &FetchOptions::default(),
+//! )
+//! .await?;
+//! for transfer in transfers.entries {
+//!     println!("{} -> {}: {}", transfer.sender, transfer.receiver, transfer.amount);
+//! }
+//! for (signature, error) in transfers.skipped {
+//!     eprintln!("Could not parse {}: {}", signature.signature, error);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod amount;
+pub mod balance_delta;
+pub mod decode;
+pub mod error;
+pub mod fungible_history;
+pub mod fungible_token_transfer;
+pub mod history;
+pub mod memo;
+pub mod models;
+pub mod retry;
+pub mod rpc;
+pub mod rpc_error;
+pub mod send;
+pub mod sol_history;
+pub mod sol_transfer;
+#[cfg(test)]
+mod test_fixtures;
+pub mod transaction;
+
+pub use error::{Error, Result};
+pub use history::{FetchOptions, ParsedHistory, SignaturePaging};
+pub use retry::RetryPolicy;
+pub use rpc::{HttpRpc, MemoryRpc, SolanaRpc, TransactionEncoding};
+pub use rpc_error::{RpcError, RpcErrorKind};
+pub use send::SendClient;
@@ -1,20 +1,28 @@
-use std::error::Error;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
 use clap::Parser;
-use solana_client::rpc_client::RpcClient;
+use prettytable::{row, Cell, Row, Table};
 use solana_sdk::{
-    signature::{Signer, write_keypair_file, read_keypair_file, Keypair},
-    system_instruction,
-    transaction::Transaction,
+    commitment_config::CommitmentConfig,
+    pubkey::Pubkey,
+    signature::{read_keypair_file, write_keypair_file, Keypair, Signer},
 };
-use spl_associated_token_account::{get_associated_token_address, instruction::create_associated_token_account};
-use spl_token::instruction::transfer;
+use solutil::amount::{format_units, SOL_DECIMALS};
+use solutil::balance_delta::{SolBalanceDelta, TokenBalanceDelta};
+use solutil::fungible_history::MintSubtotal;
+use solutil::fungible_token_transfer::FungibleTokenTransfer;
+use solutil::memo::memo_matches;
+use solutil::models::SolanaSignature;
+use solutil::sol_transfer::SolTransfer;
+use solutil::{
+    fungible_history, history, sol_history, Error, FetchOptions, HttpRpc, Result, RetryPolicy,
+    SendClient, SignaturePaging, TransactionEncoding,
+};
+use std::collections::HashMap;
+use std::error::Error as _;
+use std::path::Path;
 
-mod fungible_history;
-mod fungible_token_transfer;
-mod history;
-mod models;
-mod sol_history;
-mod sol_transfer;
+/// Directory SaveHistory writes transactions to
+const SERIALIZATIONS_DIR: &str = "serializations";
 
 /// Simple program to fetch Solana transaction history
 #[derive(Parser, Debug)]
@@ -23,6 +31,161 @@ struct Args {
     /// Command to execute
     #[clap(subcommand)]
     command: Command,
+
+    /// Commitment level for reads and transaction confirmation
+    #[arg(long = "commitment", global = true, value_enum, default_value_t = Commitment::Finalized)]
+    commitment: Commitment,
+
+    /// Encoding to fetch transactions in; base64 transactions are decoded locally
+    #[arg(long = "encoding", global = true, value_enum, default_value_t = Encoding::JsonParsed)]
+    encoding: Encoding,
+
+    #[command(flatten)]
+    retry: RetryArgs,
+}
+
+/// Commitment levels accepted by `--commitment`
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum Commitment {
+    Processed,
+    Confirmed,
+    Finalized,
+}
+
+impl From<Commitment> for CommitmentConfig {
+    fn from(commitment: Commitment) -> Self {
+        match commitment {
+            Commitment::Processed => CommitmentConfig::processed(),
+            Commitment::Confirmed => CommitmentConfig::confirmed(),
+            Commitment::Finalized => CommitmentConfig::finalized(),
+        }
+    }
+}
+
+/// Transaction encodings accepted by `--encoding`
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum Encoding {
+    JsonParsed,
+    Base64,
+}
+
+impl From<Encoding> for TransactionEncoding {
+    fn from(encoding: Encoding) -> Self {
+        match encoding {
+            Encoding::JsonParsed => TransactionEncoding::JsonParsed,
+            Encoding::Base64 => TransactionEncoding::Base64,
+        }
+    }
+}
+
+/// Retry and rate limit options applied to every RPC request
+#[derive(clap::Args, Debug)]
+struct RetryArgs {
+    /// Attempts per RPC request before giving up
+    #[arg(long = "max-attempts", global = true, default_value_t = RetryPolicy::default().max_attempts)]
+    max_attempts: u32,
+
+    /// Maximum RPC requests per second (default: unlimited)
+    #[arg(long = "rps", global = true)]
+    requests_per_second: Option<u32>,
+}
+
+impl From<RetryArgs> for RetryPolicy {
+    fn from(args: RetryArgs) -> Self {
+        RetryPolicy {
+            max_attempts: args.max_attempts.max(1),
+            requests_per_second: args.requests_per_second,
+            ..RetryPolicy::default()
+        }
+    }
+}
+
+/// Signature paging options shared by the history commands
+#[derive(clap::Args, Debug)]
+struct PagingArgs {
+    /// Maximum number of signatures to fetch (default: the full history)
+    #[arg(short = 'l', long = "limit")]
+    limit: Option<usize>,
+
+    /// Start searching backwards from this transaction signature
+    #[arg(long = "before")]
+    before: Option<String>,
+
+    /// Stop searching once this transaction signature is reached
+    #[arg(long = "until")]
+    until: Option<String>,
+
+    /// Only include transactions at or after this time (unix seconds, YYYY-MM-DD or RFC 3339)
+    #[arg(long = "since", value_parser = parse_timestamp)]
+    since: Option<i64>,
+
+    /// Only include transactions at or before this time (unix seconds, YYYY-MM-DD or RFC 3339);
+    /// a date includes that whole day
+    #[arg(long = "until-time", value_parser = parse_until_timestamp)]
+    until_time: Option<i64>,
+}
+
+/// Parses a CLI time bound into a unix timestamp. Dates without a time are
+/// taken as midnight UTC.
+fn parse_timestamp(value: &str) -> std::result::Result<i64, String> {
+    if let Ok(seconds) = value.parse::<i64>() {
+        return Ok(seconds);
+    }
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(date_time.timestamp());
+    }
+    if let Ok(date_time) = NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S") {
+        return Ok(date_time.and_utc().timestamp());
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_time(NaiveTime::MIN).and_utc().timestamp());
+    }
+    Err(format!(
+        "invalid time '{}': expected unix seconds, YYYY-MM-DD or RFC 3339",
+        value
+    ))
+}
+
+/// Parses the `--until-time` bound. A date without a time covers the whole
+/// day, so it's taken as the last second of that day in UTC.
+fn parse_until_timestamp(value: &str) -> std::result::Result<i64, String> {
+    match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        Ok(date) => Ok(date.and_time(NaiveTime::MIN).and_utc().timestamp() + 24 * 60 * 60 - 1),
+        Err(_) => parse_timestamp(value),
+    }
+}
+
+impl From<PagingArgs> for SignaturePaging {
+    fn from(args: PagingArgs) -> Self {
+        SignaturePaging {
+            limit: args.limit,
+            before: args.before,
+            until: args.until,
+            since: args.since,
+            until_time: args.until_time,
+        }
+    }
+}
+
+/// Transaction fetching options shared by the history commands
+#[derive(clap::Args, Debug)]
+struct FetchArgs {
+    /// Number of getTransaction calls grouped into one JSON-RPC batch
+    #[arg(long = "batch-size", default_value_t = FetchOptions::default().batch_size)]
+    batch_size: usize,
+
+    /// Maximum number of RPC requests in flight at once
+    #[arg(long = "concurrency", default_value_t = FetchOptions::default().concurrency)]
+    concurrency: usize,
+}
+
+impl From<FetchArgs> for FetchOptions {
+    fn from(args: FetchArgs) -> Self {
+        FetchOptions {
+            batch_size: args.batch_size,
+            concurrency: args.concurrency,
+        }
+    }
 }
 
 #[derive(Parser, Debug)]
@@ -36,6 +199,12 @@ enum Command {
         /// Solana address
         #[arg(short = 'a', long = "address")]
         solana_address: String,
+
+        #[command(flatten)]
+        paging: PagingArgs,
+
+        #[command(flatten)]
+        fetch: FetchArgs,
     },
     /// Fetch SOL transaction history
     SOLHistory {
@@ -46,6 +215,20 @@ enum Command {
         /// Solana address
         #[arg(short = 'a', long = "address")]
         solana_address: String,
+
+        /// Report net balance changes from pre/post balances instead of parsed transfers
+        #[arg(long = "balance-deltas")]
+        balance_deltas: bool,
+
+        /// Only show transactions whose memo contains this text (case-insensitive)
+        #[arg(long = "memo")]
+        memo: Option<String>,
+
+        #[command(flatten)]
+        paging: PagingArgs,
+
+        #[command(flatten)]
+        fetch: FetchArgs,
     },
     /// Fetch fungible token transaction history
     FungibleHistory {
@@ -57,9 +240,23 @@ enum Command {
         #[arg(short = 'a', long = "address")]
         solana_address: String,
 
-        /// Mint address of the fungible token
+        /// Mint address of the fungible token (default: every token the wallet holds)
         #[arg(short = 'm', long = "mint")]
-        mint_address: String,
+        mint_address: Option<String>,
+
+        /// Report net balance changes from pre/post balances instead of parsed transfers
+        #[arg(long = "balance-deltas")]
+        balance_deltas: bool,
+
+        /// Only show transactions whose memo contains this text (case-insensitive)
+        #[arg(long = "memo")]
+        memo: Option<String>,
+
+        #[command(flatten)]
+        paging: PagingArgs,
+
+        #[command(flatten)]
+        fetch: FetchArgs,
     },
     /// Send SOL from one account to another
     Send {
@@ -119,37 +316,191 @@ enum Command {
     },
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    // Initialize the logger
-    env_logger::init();
+#[tokio::main]
+async fn main() {
+    // Initialize the logger, showing warnings unless RUST_LOG says otherwise
+    env_logger::init_from_env(env_logger::Env::default().default_filter_or("warn"));
 
     // Parse arguments
     let args = Args::parse();
 
+    if let Err(error) = run(args).await {
+        eprintln!("Error: {}", describe(&error));
+        std::process::exit(exit_code(&error));
+    }
+}
+
+/// Formats an error followed by its chain of causes.
+fn describe(error: &Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(&format!(": {}", cause));
+        source = cause.source();
+    }
+    message
+}
+
+/// Lists the transactions a history command left out on stderr.
+fn report_skipped(skipped: &[(SolanaSignature, Error)]) {
+    if skipped.is_empty() {
+        return;
+    }
+    eprintln!(
+        "Skipped {} transaction(s) that could not be parsed:",
+        skipped.len()
+    );
+    for (signature, error) in skipped {
+        eprintln!("  {}: {}", signature.signature, describe(error));
+    }
+}
+
+/// Maps each kind of failure to its own process exit code, so scripts can
+/// tell bad input from network or node problems.
+fn exit_code(error: &Error) -> i32 {
+    match error {
+        Error::Validation(_) => 2,
+        Error::Transport(_) => 3,
+        Error::Rpc(_) => 4,
+        Error::Client(_) => 5,
+        Error::Parse { .. } => 6,
+        Error::Signing(_) => 7,
+        Error::Io(_) => 8,
+    }
+}
+
+fn read_keypair(path: &str) -> Result<Keypair> {
+    read_keypair_file(path)
+        .map_err(|e| Error::Validation(format!("cannot read keypair {}: {}", path, e)))
+}
+
+fn parse_pubkey(value: &str) -> Result<Pubkey> {
+    value
+        .parse()
+        .map_err(|e| Error::Validation(format!("invalid address {}: {}", value, e)))
+}
+
+async fn run(args: Args) -> Result<()> {
+    let commitment: CommitmentConfig = args.commitment.into();
+    let retry_policy: RetryPolicy = args.retry.into();
+    let encoding: TransactionEncoding = args.encoding.into();
+
     match args.command {
         Command::SOLHistory {
             solana_rpc_url,
             solana_address,
+            balance_deltas,
+            memo,
+            paging,
+            fetch,
         } => {
-            sol_history::print_sol_transfer_history(&solana_rpc_url, &solana_address)?;
+            println!(
+                "Print sol history for {} via {}",
+                solana_address, solana_rpc_url
+            );
+            let rpc = HttpRpc::new(&solana_rpc_url, retry_policy, commitment)
+                .with_transaction_encoding(encoding);
+            let paging = paging.into();
+            let fetch = fetch.into();
+            if balance_deltas {
+                let mut history =
+                    sol_history::fetch_sol_balance_history(&rpc, &solana_address, &paging, &fetch)
+                        .await?;
+                if let Some(pattern) = &memo {
+                    history
+                        .entries
+                        .retain(|delta| memo_matches(delta.memo.as_deref(), pattern));
+                }
+                print_sol_balance_deltas(&history.entries);
+                report_skipped(&history.skipped);
+            } else {
+                let mut history =
+                    sol_history::fetch_sol_transfer_history(&rpc, &solana_address, &paging, &fetch)
+                        .await?;
+                if let Some(pattern) = &memo {
+                    history
+                        .entries
+                        .retain(|transfer| memo_matches(transfer.memo.as_deref(), pattern));
+                }
+                print_sol_transfers(&history.entries);
+                report_skipped(&history.skipped);
+            }
         }
         Command::SaveHistory {
             solana_rpc_url,
             solana_address,
+            paging,
+            fetch,
         } => {
-            history::fetch_transaction_history(&solana_rpc_url, &solana_address)?;
+            history::save_transaction_history(
+                &HttpRpc::new(&solana_rpc_url, retry_policy, commitment)
+                    .with_transaction_encoding(encoding),
+                &solana_address,
+                &paging.into(),
+                &fetch.into(),
+                Path::new(SERIALIZATIONS_DIR),
+            )
+            .await?;
         }
         Command::FungibleHistory {
             solana_rpc_url,
             solana_address,
             mint_address,
+            balance_deltas,
+            memo,
+            paging,
+            fetch,
         } => {
-            // Logic to fetch fungible token transaction history
-            fungible_history::print_fungible_transfer_history(
-                &solana_rpc_url,
-                &solana_address,
-                &mint_address,
-            )?;
+            println!(
+                "\nFetching fungible token {} transaction history for {} via {}\n",
+                mint_address.as_deref().unwrap_or("(all mints)"),
+                solana_address,
+                solana_rpc_url
+            );
+            let rpc = HttpRpc::new(&solana_rpc_url, retry_policy, commitment)
+                .with_transaction_encoding(encoding);
+            let paging = paging.into();
+            let fetch = fetch.into();
+            if balance_deltas {
+                let mut history = fungible_history::fetch_token_balance_history(
+                    &rpc,
+                    &solana_address,
+                    mint_address.as_deref(),
+                    &paging,
+                    &fetch,
+                )
+                .await?;
+                if let Some(pattern) = &memo {
+                    history
+                        .entries
+                        .retain(|delta| memo_matches(delta.memo.as_deref(), pattern));
+                }
+                let subtotals = fungible_history::balance_delta_subtotals(&history.entries);
+                let symbols = fetch_mint_symbols(&rpc, &subtotals).await?;
+                print_token_balance_deltas(&history.entries, &symbols);
+                print_mint_subtotals(&subtotals, &symbols);
+                report_skipped(&history.skipped);
+            } else {
+                let mut history = fungible_history::fetch_fungible_transfer_history(
+                    &rpc,
+                    &solana_address,
+                    mint_address.as_deref(),
+                    &paging,
+                    &fetch,
+                )
+                .await?;
+                if let Some(pattern) = &memo {
+                    history
+                        .entries
+                        .retain(|transfer| memo_matches(transfer.memo.as_deref(), pattern));
+                }
+                let subtotals =
+                    fungible_history::transfer_subtotals(&history.entries, &solana_address);
+                let symbols = fetch_mint_symbols(&rpc, &subtotals).await?;
+                print_fungible_transfers(&history.entries, &symbols);
+                print_mint_subtotals(&subtotals, &symbols);
+                report_skipped(&history.skipped);
+            }
         }
         Command::Send {
             solana_rpc_url,
@@ -162,22 +513,14 @@ fn main() -> Result<(), Box<dyn Error>> {
                 amount, keypair, recipient, solana_rpc_url
             );
 
-            let rpc_client = RpcClient::new(solana_rpc_url);
-            let sender_keypair = read_keypair_file(&keypair)?;
-            let recipient_pubkey = recipient.parse()?;
+            let client = SendClient::new(&solana_rpc_url, commitment, retry_policy);
+            let sender_keypair = read_keypair(&keypair)?;
+            let recipient_pubkey = parse_pubkey(&recipient)?;
             let lamports = (amount * 1_000_000_000.0) as u64; // Convert SOL to lamports
 
-            let recent_blockhash = rpc_client.get_latest_blockhash()?;
-            let transfer_instruction =
-                system_instruction::transfer(&sender_keypair.pubkey(), &recipient_pubkey, lamports);
-            let transaction = Transaction::new_signed_with_payer(
-                &[transfer_instruction],
-                Some(&sender_keypair.pubkey()),
-                &[&sender_keypair],
-                recent_blockhash,
-            );
-
-            let signature = rpc_client.send_and_confirm_transaction(&transaction)?;
+            let signature = client
+                .send_sol(&sender_keypair, &recipient_pubkey, lamports)
+                .await?;
             println!("Transaction sent successfully. Signature: {}", signature);
         }
         Command::SendFungible {
@@ -193,59 +536,244 @@ fn main() -> Result<(), Box<dyn Error>> {
                 amount, keypair, recipient, solana_rpc_url, mint_address, decimals
             );
 
-            let rpc_client = RpcClient::new(solana_rpc_url);
-            let sender_keypair = read_keypair_file(&keypair)?;
-            let recipient_pubkey = recipient.parse()?;
-            let token_program_id = spl_token_2022::id();
-            let mint_pubkey = mint_address.parse()?;
-            let sender_token_account = get_associated_token_address(&sender_keypair.pubkey(), &mint_pubkey);
-            let recipient_token_account = get_associated_token_address(&recipient_pubkey, &mint_pubkey);
+            let client = SendClient::new(&solana_rpc_url, commitment, retry_policy);
+            let sender_keypair = read_keypair(&keypair)?;
+            let recipient_pubkey = parse_pubkey(&recipient)?;
+            let mint_pubkey = parse_pubkey(&mint_address)?;
             let amount = (amount * 10f64.powi(decimals as i32)) as u64; // Convert amount using specified decimal places
 
-            // Create associated token account for recipient if it doesn't exist
-            let mut instructions = vec![];
-            if rpc_client.get_account(&recipient_token_account).is_err() {
-                let create_account_instruction = create_associated_token_account(
-                    &sender_keypair.pubkey(),
-                    &recipient_pubkey,
-                    &mint_pubkey,
-                    &token_program_id,
-                );
-                instructions.push(create_account_instruction);
-            }
-
-            // Transfer instruction
-            let transfer_instruction = transfer(
-                &token_program_id,
-                &sender_token_account,
-                &recipient_token_account,
-                &sender_keypair.pubkey(),
-                &[&sender_keypair.pubkey()],
-                amount,
-            )?;
-            instructions.push(transfer_instruction);
-
-            let recent_blockhash = rpc_client.get_latest_blockhash()?;
-            let transaction = Transaction::new_signed_with_payer(
-                &instructions,
-                Some(&sender_keypair.pubkey()),
-                &[&sender_keypair],
-                recent_blockhash,
-            );
-
-            let signature = rpc_client.send_and_confirm_transaction(&transaction)?;
+            let signature = client
+                .send_fungible(&sender_keypair, &recipient_pubkey, &mint_pubkey, amount)
+                .await?;
             println!("Transaction sent successfully. Signature: {}", signature);
         }
         Command::GenerateKeypair { file_path } => {
             let new_keypair = Keypair::new();
-            write_keypair_file(&new_keypair, &file_path)?;
+            write_keypair_file(&new_keypair, &file_path)
+                .map_err(|e| Error::Io(std::io::Error::other(e.to_string())))?;
             println!("New keypair generated and saved to {}", file_path);
         }
         Command::Inspect { keypair_file } => {
-            let keypair = read_keypair_file(&keypair_file)?;
+            let keypair = read_keypair(&keypair_file)?;
             println!("Solana Address: {}", keypair.pubkey());
         }
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+fn print_sol_transfers(transfers: &[SolTransfer]) {
+    let mut table = Table::new();
+    table.add_row(row![
+        "Tx ID",
+        "Ix",
+        "Depth",
+        "Kind",
+        "Sender",
+        "Receiver",
+        "Amount",
+        "Fee Payer",
+        "Timestamp",
+        "Memo"
+    ]);
+
+    for sol_transfer in transfers {
+        #[allow(deprecated)]
+        let timestamp = Utc.timestamp(sol_transfer.timestamp as i64, 0);
+        table.add_row(row!(
+            sol_transfer.transaction_id[0..10],
+            sol_transfer.instruction_index,
+            sol_transfer.stack_height,
+            sol_transfer.kind,
+            sol_transfer.sender,
+            sol_transfer.receiver,
+            sol_transfer.amount as f64 / 1_000_000_000.0,
+            sol_transfer.fee_payer,
+            timestamp.to_string(),
+            sol_transfer.memo.as_deref().unwrap_or_default()
+        ));
+    }
+
+    table.printstd();
+}
+
+/// Shows the owner wallet above the token account it holds.
+fn token_party(owner: Option<&str>, account: Option<&str>) -> String {
+    match (owner, account) {
+        (Some(owner), Some(account)) => format!("{}\n(account {})", owner, account),
+        (None, Some(account)) => account.to_string(),
+        (_, None) => "-".to_string(),
+    }
+}
+
+fn print_fungible_transfers(
+    transfers: &[FungibleTokenTransfer],
+    symbols: &HashMap<String, String>,
+) {
+    let mut table = Table::new();
+    table.add_row(row![
+        "Tx ID",
+        "Ix",
+        "Depth",
+        "Event",
+        "Mint",
+        "Sender",
+        "Receiver",
+        "Amount",
+        "Timestamp",
+        "Memo"
+    ]);
+
+    for transfer in transfers {
+        #[allow(deprecated)]
+        let timestamp = Utc.timestamp(transfer.timestamp as i64, 0);
+        table.add_row(Row::new(vec![
+            Cell::new(&transfer.transaction_id[0..10]),
+            Cell::new(&transfer.instruction_index.to_string()),
+            Cell::new(&transfer.stack_height.to_string()),
+            Cell::new(&transfer.kind.to_string()),
+            Cell::new(mint_label(&transfer.mint, symbols)),
+            Cell::new(&token_party(
+                transfer.sender_owner.as_deref(),
+                transfer.sender.as_deref(),
+            )),
+            Cell::new(&token_party(
+                transfer.receiver_owner.as_deref(),
+                transfer.receiver.as_deref(),
+            )),
+            Cell::new(&transfer.ui_amount().unwrap_or_default()),
+            Cell::new(&timestamp.to_string()),
+            Cell::new(transfer.memo.as_deref().unwrap_or_default()),
+        ]));
+    }
+
+    table.printstd();
+}
+
+fn print_sol_balance_deltas(deltas: &[SolBalanceDelta]) {
+    let mut table = Table::new();
+    table.add_row(row![
+        "Tx ID",
+        "Pre",
+        "Post",
+        "Change",
+        "Fee",
+        "Timestamp",
+        "Memo"
+    ]);
+
+    for delta in deltas {
+        #[allow(deprecated)]
+        let timestamp = Utc.timestamp(delta.timestamp as i64, 0);
+        let fee = delta
+            .fee
+            .map(|fee| format_units(fee as i128, SOL_DECIMALS))
+            .unwrap_or_default();
+        table.add_row(Row::new(vec![
+            Cell::new(&delta.transaction_id[0..10]),
+            Cell::new(&format_units(delta.pre_balance as i128, SOL_DECIMALS)),
+            Cell::new(&format_units(delta.post_balance as i128, SOL_DECIMALS)),
+            Cell::new(&format_units(delta.change(), SOL_DECIMALS)),
+            Cell::new(&fee),
+            Cell::new(&timestamp.to_string()),
+            Cell::new(delta.memo.as_deref().unwrap_or_default()),
+        ]));
+    }
+
+    table.printstd();
+}
+
+fn print_token_balance_deltas(deltas: &[TokenBalanceDelta], symbols: &HashMap<String, String>) {
+    let mut table = Table::new();
+    table.add_row(row![
+        "Tx ID",
+        "Mint",
+        "Token Account",
+        "Pre",
+        "Post",
+        "Change",
+        "Timestamp",
+        "Memo"
+    ]);
+
+    for delta in deltas {
+        #[allow(deprecated)]
+        let timestamp = Utc.timestamp(delta.timestamp as i64, 0);
+        table.add_row(Row::new(vec![
+            Cell::new(&delta.transaction_id[0..10]),
+            Cell::new(mint_label(&delta.mint, symbols)),
+            Cell::new(&delta.account),
+            Cell::new(&format_units(delta.pre_amount as i128, delta.decimals)),
+            Cell::new(&format_units(delta.post_amount as i128, delta.decimals)),
+            Cell::new(&format_units(delta.change(), delta.decimals)),
+            Cell::new(&timestamp.to_string()),
+            Cell::new(delta.memo.as_deref().unwrap_or_default()),
+        ]));
+    }
+
+    table.printstd();
+}
+
+/// Looks up symbols for the mints in `subtotals`.
+async fn fetch_mint_symbols(
+    rpc: &HttpRpc,
+    subtotals: &[MintSubtotal],
+) -> Result<HashMap<String, String>> {
+    let mints: Vec<String> = subtotals
+        .iter()
+        .map(|subtotal| subtotal.mint.clone())
+        .collect();
+    fungible_history::mint_symbols(rpc, &mints).await
+}
+
+/// Shows a mint by its symbol where one is known.
+fn mint_label<'a>(mint: &'a str, symbols: &'a HashMap<String, String>) -> &'a str {
+    symbols.get(mint).map_or(mint, String::as_str)
+}
+
+fn print_mint_subtotals(subtotals: &[MintSubtotal], symbols: &HashMap<String, String>) {
+    let mut table = Table::new();
+    table.add_row(row!["Mint", "Received", "Sent", "Net"]);
+
+    for subtotal in subtotals {
+        let format = |raw: i128| match subtotal.decimals {
+            Some(decimals) => format_units(raw, decimals),
+            None => format!("{} (base units)", raw),
+        };
+        table.add_row(Row::new(vec![
+            Cell::new(mint_label(&subtotal.mint, symbols)),
+            Cell::new(&format(subtotal.received as i128)),
+            Cell::new(&format(subtotal.sent as i128)),
+            Cell::new(&format(subtotal.net())),
+        ]));
+    }
+
+    table.printstd();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_time_bounds() {
+        assert_eq!(parse_timestamp("1700000000"), Ok(1_700_000_000));
+        assert_eq!(parse_timestamp("2024-09-30"), Ok(1_727_654_400));
+        assert_eq!(parse_timestamp("2024-09-30T12:00:00"), Ok(1_727_697_600));
+        assert_eq!(
+            parse_timestamp("2024-09-30T12:00:00+02:00"),
+            Ok(1_727_690_400)
+        );
+        assert!(parse_timestamp("30/09/2024").is_err());
+    }
+
+    #[test]
+    fn until_time_date_covers_the_whole_day() {
+        assert_eq!(parse_until_timestamp("2024-09-30"), Ok(1_727_740_799));
+        assert_eq!(
+            parse_until_timestamp("2024-09-30T12:00:00Z"),
+            Ok(1_727_697_600)
+        );
+        assert_eq!(parse_until_timestamp("1700000000"), Ok(1_700_000_000));
+    }
+}
@@ -0,0 +1,75 @@
+use crate::models::{Instruction, ParsedInfo, SolanaTransactionDetails};
+use crate::transaction::instructions;
+
+/// Returns the text of a transaction's SPL Memo instructions, top-level or
+/// inner, joined with `"; "`, or `None` if it has none.
+///
+/// This is the memo the parsed transfers and balance changes carry. The
+/// history commands fall back to the memo `getSignaturesForAddress`
+/// reported for transactions without a parseable memo instruction, such as
+/// memos logged by programs other than SPL Memo.
+pub fn transaction_memo(transaction: &SolanaTransactionDetails) -> Option<String> {
+    let memos: Vec<&str> = instructions(transaction)
+        .iter()
+        .filter_map(|instruction_ref| match instruction_ref.instruction {
+            Instruction::Parsed(parsed) => match &parsed.parsed {
+                ParsedInfo::Memo(memo) => Some(memo.as_str()),
+                _ => None,
+            },
+            Instruction::PartiallyDecoded(_) => None,
+        })
+        .collect();
+    (!memos.is_empty()).then(|| memos.join("; "))
+}
+
+/// Returns true if `memo` contains `pattern`, ignoring case. Entries
+/// without a memo never match.
+pub fn memo_matches(memo: Option<&str>, pattern: &str) -> bool {
+    memo.is_some_and(|memo| memo.to_lowercase().contains(&pattern.to_lowercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{memo, system_transfer, transaction};
+    use crate::transaction::parse_transaction;
+    use serde_json::json;
+
+    #[test]
+    fn joins_top_level_and_inner_memos() {
+        let mut json = transaction(
+            "Sig1",
+            Some(100),
+            &["Alice", "Bob"],
+            vec![memo("invoice 42"), system_transfer("Alice", "Bob", 1_000)],
+        );
+        let mut inner = memo("paid");
+        inner["stackHeight"] = json!(2);
+        json["meta"]["innerInstructions"] = json!([{ "index": 1, "instructions": [inner] }]);
+
+        let transaction = parse_transaction(&json).unwrap();
+        assert_eq!(
+            transaction_memo(&transaction).as_deref(),
+            Some("invoice 42; paid")
+        );
+    }
+
+    #[test]
+    fn transactions_without_memo_instructions_have_no_memo() {
+        let json = transaction(
+            "Sig1",
+            Some(100),
+            &["Alice", "Bob"],
+            vec![system_transfer("Alice", "Bob", 1_000)],
+        );
+        assert_eq!(transaction_memo(&parse_transaction(&json).unwrap()), None);
+    }
+
+    #[test]
+    fn matches_memos_ignoring_case() {
+        assert!(memo_matches(Some("Invoice 42"), "invoice"));
+        assert!(memo_matches(Some("invoice 42"), "VOICE 4"));
+        assert!(!memo_matches(Some("invoice 42"), "refund"));
+        assert!(!memo_matches(None, ""));
+    }
+}
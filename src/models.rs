@@ -1,7 +1,7 @@
-#![allow(dead_code)]
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct SolanaSignature {
     pub signature: String,
     pub slot: u64,
@@ -9,117 +9,392 @@ pub struct SolanaSignature {
     pub block_time: Option<i64>,
     #[serde(rename = "confirmationStatus")]
     pub confirmation_status: Option<String>,
-    pub err: Option<serde_json::Value>,
+    pub err: Option<Value>,
     pub memo: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+/// A `getTransaction` result in `jsonParsed` encoding, for legacy and v0
+/// transactions. Binary-encoded results are decoded into the same shape by
+/// `decode::decode_transaction`.
+#[derive(Debug, Clone, Deserialize)]
 pub struct SolanaTransactionDetails {
-    #[serde(rename = "meta")]
-    pub meta: TransactionMeta,
-    #[serde(rename = "transaction")]
+    pub slot: u64,
+    #[serde(rename = "blockTime")]
+    pub block_time: Option<i64>,
+    /// Missing for transactions whose status wasn't recorded.
+    pub meta: Option<TransactionMeta>,
     pub transaction: Transaction,
-    pub instructions: Vec<ParsedInstruction>,
+    /// `None` for nodes that predate versioned transactions.
+    pub version: Option<TransactionVersion>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum TransactionVersion {
+    /// Always `"legacy"`.
+    Legacy(String),
+    Number(u8),
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct TransactionMeta {
-    #[serde(rename = "postBalances")]
-    pub post_balances: Vec<u64>,
-    #[serde(rename = "preBalances")]
+    /// Error the transaction failed with, `None` if it succeeded.
+    pub err: Option<Value>,
+    /// Fee in lamports, charged to the fee payer.
+    #[serde(default)]
+    pub fee: u64,
+    #[serde(rename = "preBalances", default)]
     pub pre_balances: Vec<u64>,
-    #[serde(rename = "status")]
-    pub status: Status,
+    #[serde(rename = "postBalances", default)]
+    pub post_balances: Vec<u64>,
+    #[serde(
+        rename = "preTokenBalances",
+        default,
+        deserialize_with = "null_as_default"
+    )]
+    pub pre_token_balances: Vec<TokenBalance>,
+    #[serde(
+        rename = "postTokenBalances",
+        default,
+        deserialize_with = "null_as_default"
+    )]
+    pub post_token_balances: Vec<TokenBalance>,
+    #[serde(
+        rename = "innerInstructions",
+        default,
+        deserialize_with = "null_as_default"
+    )]
+    pub inner_instructions: Vec<InnerInstructions>,
+    #[serde(rename = "logMessages", default, deserialize_with = "null_as_default")]
+    pub log_messages: Vec<String>,
+    /// Addresses loaded from lookup tables by v0 transactions.
+    #[serde(
+        rename = "loadedAddresses",
+        default,
+        deserialize_with = "null_as_default"
+    )]
+    pub loaded_addresses: LoadedAddresses,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct Status {
-    #[serde(rename = "Ok")]
-    pub ok: Option<serde_json::Value>,
-    #[serde(rename = "Err")]
-    pub err: Option<serde_json::Value>,
+/// Instructions invoked through CPI by one top-level instruction.
+#[derive(Debug, Clone, Deserialize)]
+pub struct InnerInstructions {
+    /// Index of the top-level instruction.
+    pub index: usize,
+    pub instructions: Vec<Instruction>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LoadedAddresses {
+    #[serde(default)]
+    pub writable: Vec<String>,
+    #[serde(default)]
+    pub readonly: Vec<String>,
+}
+
+/// A token account balance recorded before or after a transaction.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TokenBalance {
+    /// Index of the token account in the transaction's account keys.
+    #[serde(rename = "accountIndex")]
+    pub account_index: usize,
+    pub mint: String,
+    pub owner: Option<String>,
+    #[serde(rename = "programId")]
+    pub program_id: Option<String>,
+    #[serde(rename = "uiTokenAmount")]
+    pub ui_token_amount: UiTokenAmount,
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct Transaction {
-    #[serde(rename = "message")]
+    pub signatures: Vec<String>,
     pub message: Message,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct Message {
+    /// Static keys; `jsonParsed` also lists keys loaded from lookup tables
+    /// here, marked with `source: "lookupTable"`.
     #[serde(rename = "accountKeys")]
     pub account_keys: Vec<AccountKey>,
-    #[serde(rename = "instructions")]
+    #[serde(rename = "recentBlockhash")]
+    pub recent_blockhash: String,
     pub instructions: Vec<Instruction>,
+    /// Lookup tables used by v0 messages; absent for legacy ones.
+    #[serde(rename = "addressTableLookups")]
+    pub address_table_lookups: Option<Vec<AddressTableLookup>>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct AccountKey {
     pub pubkey: String,
     pub signer: bool,
-    pub source: String,
     pub writable: bool,
+    /// `"transaction"` or `"lookupTable"`; missing on older nodes.
+    pub source: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct Instruction {
-    #[serde(rename = "parsed")]
-    pub parsed: Option<ParsedInstruction>,
+#[derive(Debug, Clone, Deserialize)]
+pub struct AddressTableLookup {
+    #[serde(rename = "accountKey")]
+    pub account_key: String,
+    #[serde(rename = "writableIndexes")]
+    pub writable_indexes: Vec<u8>,
+    #[serde(rename = "readonlyIndexes")]
+    pub readonly_indexes: Vec<u8>,
 }
 
-#[derive(Debug, Deserialize)]
+/// An instruction as returned in `jsonParsed` encoding: decoded if the node
+/// knows the program, raw otherwise.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Instruction {
+    Parsed(ParsedInstruction),
+    PartiallyDecoded(PartiallyDecodedInstruction),
+}
+
+impl Instruction {
+    pub fn program_id(&self) -> &str {
+        match self {
+            Instruction::Parsed(instruction) => &instruction.program_id,
+            Instruction::PartiallyDecoded(instruction) => &instruction.program_id,
+        }
+    }
+
+    /// Invocation depth reported by the node; `None` for top-level
+    /// instructions and on nodes older than 1.14.
+    pub fn stack_height(&self) -> Option<u32> {
+        match self {
+            Instruction::Parsed(instruction) => instruction.stack_height,
+            Instruction::PartiallyDecoded(instruction) => instruction.stack_height,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(from = "RawParsedInstruction")]
 pub struct ParsedInstruction {
-    #[serde(rename = "info")]
-    pub info: Info,
-    pub parsed: Option<InstructionInfo>,
+    /// Program name, e.g. `"system"` or `"spl-token"`.
+    pub program: String,
+    pub program_id: String,
+    pub parsed: ParsedInfo,
+    pub stack_height: Option<u32>,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct InstructionInfo {
-    pub info: InfoDetails,
-    pub r#type: String,
+#[derive(Deserialize)]
+struct RawParsedInstruction {
+    program: String,
+    #[serde(rename = "programId")]
+    program_id: String,
+    parsed: Value,
+    #[serde(rename = "stackHeight")]
+    stack_height: Option<u32>,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct InfoDetails {
-    pub mint: Option<String>,
-    #[serde(rename = "newAccount")]
-    pub new_account: Option<String>,
-    pub owner: Option<String>,
-    pub source: Option<String>,
-    #[serde(rename = "tokenAmount")]
-    pub token_amount: Option<UiTokenAmount>,
+impl From<RawParsedInstruction> for ParsedInstruction {
+    fn from(raw: RawParsedInstruction) -> Self {
+        let parsed = match raw.program.as_str() {
+            "system" => SystemInstruction::deserialize(&raw.parsed)
+                .map(ParsedInfo::System)
+                .ok(),
+            "spl-token" | "spl-token-2022" => TokenInstruction::deserialize(&raw.parsed)
+                .map(ParsedInfo::Token)
+                .ok(),
+            "spl-memo" => raw
+                .parsed
+                .as_str()
+                .map(|memo| ParsedInfo::Memo(memo.to_string())),
+            _ => None,
+        };
+        ParsedInstruction {
+            parsed: parsed.unwrap_or(ParsedInfo::Other(raw.parsed)),
+            program: raw.program,
+            program_id: raw.program_id,
+            stack_height: raw.stack_height,
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
+/// The `parsed` field of a parsed instruction. Instructions this crate
+/// doesn't model are kept as `Other`.
+#[derive(Debug, Clone)]
+pub enum ParsedInfo {
+    System(SystemInstruction),
+    /// SPL Token or Token-2022.
+    Token(TokenInstruction),
+    /// SPL Memo, whose `parsed` field is the memo text.
+    Memo(String),
+    Other(Value),
+}
+
+/// System program instructions that move lamports.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(
+    tag = "type",
+    content = "info",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum SystemInstruction {
+    Transfer {
+        source: String,
+        destination: String,
+        lamports: u64,
+    },
+    TransferWithSeed {
+        source: String,
+        destination: String,
+        lamports: u64,
+    },
+    CreateAccount {
+        source: String,
+        new_account: String,
+        lamports: u64,
+    },
+    CreateAccountWithSeed {
+        source: String,
+        new_account: String,
+        lamports: u64,
+    },
+    WithdrawFromNonce {
+        nonce_account: String,
+        destination: String,
+        lamports: u64,
+    },
+}
+
+/// SPL Token and Token-2022 instructions. Raw amounts are in base units;
+/// `*Checked` variants also carry the mint and its decimals.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(
+    tag = "type",
+    content = "info",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum TokenInstruction {
+    Transfer {
+        source: String,
+        destination: String,
+        #[serde(deserialize_with = "u64_from_string")]
+        amount: u64,
+    },
+    TransferChecked {
+        source: String,
+        destination: String,
+        mint: String,
+        token_amount: UiTokenAmount,
+    },
+    MintTo {
+        mint: String,
+        account: String,
+        #[serde(deserialize_with = "u64_from_string")]
+        amount: u64,
+    },
+    MintToChecked {
+        mint: String,
+        account: String,
+        token_amount: UiTokenAmount,
+    },
+    Burn {
+        account: String,
+        mint: String,
+        #[serde(deserialize_with = "u64_from_string")]
+        amount: u64,
+    },
+    BurnChecked {
+        account: String,
+        mint: String,
+        token_amount: UiTokenAmount,
+    },
+    CloseAccount {
+        account: String,
+        destination: String,
+    },
+    Approve {
+        source: String,
+        delegate: String,
+        #[serde(deserialize_with = "u64_from_string")]
+        amount: u64,
+    },
+    ApproveChecked {
+        source: String,
+        delegate: String,
+        mint: String,
+        token_amount: UiTokenAmount,
+    },
+    Revoke {
+        source: String,
+    },
+    /// Sets an authority of either a token account or a mint.
+    SetAuthority {
+        account: Option<String>,
+        mint: Option<String>,
+        new_authority: Option<String>,
+        authority_type: String,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PartiallyDecodedInstruction {
+    #[serde(rename = "programId")]
+    pub program_id: String,
+    pub accounts: Vec<String>,
+    /// Instruction data, base58 encoded.
+    pub data: String,
+    #[serde(rename = "stackHeight")]
+    pub stack_height: Option<u32>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct UiTokenAmount {
+    /// Raw amount in base units.
     pub amount: String,
     pub decimals: u8,
     #[serde(rename = "uiAmount")]
-    pub ui_amount: f64,
+    pub ui_amount: Option<f64>,
     #[serde(rename = "uiAmountString")]
     pub ui_amount_string: String,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct Info {
-    #[serde(rename = "destination")]
-    pub destination: Option<String>,
-    #[serde(rename = "source")]
-    pub source: Option<String>,
-    #[serde(rename = "lamports")]
-    pub lamports: Option<u64>,
-    // Add new fields
-    pub mint: Option<String>,
-    #[serde(rename = "newAccount")]
-    pub new_account: Option<String>,
-    pub owner: Option<String>,
-    #[serde(rename = "tokenAmount")]
-    pub token_amount: Option<UiTokenAmount>,
+impl UiTokenAmount {
+    /// The raw amount as a number, `None` if it isn't one.
+    pub fn raw_amount(&self) -> Option<u64> {
+        self.amount.parse().ok()
+    }
 }
 
+/// A JSON-RPC response envelope: exactly one of `result` or `error` is set.
 #[derive(Debug, Deserialize)]
 pub struct RpcResponse<T> {
-    pub result: T,
+    pub id: Option<u64>,
+    pub result: Option<T>,
+    pub error: Option<RpcErrorObject>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RpcErrorObject {
+    pub code: i64,
+    pub message: String,
+    pub data: Option<Value>,
+}
+
+/// Reads `null` as the type's default, for fields nodes may send as `null`.
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// Reads a `u64` sent as a decimal string, as token amounts are.
+fn u64_from_string<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    String::deserialize(deserializer)?
+        .parse()
+        .map_err(serde::de::Error::custom)
 }
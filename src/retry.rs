@@ -0,0 +1,227 @@
+use chrono::{DateTime, Utc};
+use log::warn;
+use rand::Rng;
+use reqwest::{Response, StatusCode};
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use crate::rpc_error::{RpcError, RpcErrorKind};
+
+/// How failed RPC requests are retried.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total attempts per request, including the first one.
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubled on every further attempt.
+    pub base_delay: Duration,
+    /// Upper bound for a single backoff delay.
+    pub max_delay: Duration,
+    /// Request budget shared by all calls of one client; `None` is unlimited.
+    pub requests_per_second: Option<u32>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            requests_per_second: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns the jittered delay to wait after failed attempt number `attempt`
+    /// (starting at 1): half of the exponential delay plus a random share of
+    /// the other half, so concurrent clients don't retry in lockstep.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+        let half = exponential / 2;
+        let jitter = rand::thread_rng().gen_range(0..=half.as_millis() as u64);
+        half + Duration::from_millis(jitter)
+    }
+}
+
+/// Spaces requests evenly to stay within a per-second budget.
+pub struct RateLimiter {
+    interval: Option<Duration>,
+    next_slot: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: Option<u32>) -> Self {
+        RateLimiter {
+            interval: requests_per_second
+                .filter(|rps| *rps > 0)
+                .map(|rps| Duration::from_secs(1) / rps),
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Waits until the next request slot is free.
+    pub async fn acquire(&self) {
+        let Some(interval) = self.interval else {
+            return;
+        };
+
+        let slot = {
+            let mut next_slot = self.next_slot.lock().await;
+            let slot = (*next_slot).max(Instant::now());
+            *next_slot = slot + interval;
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+/// Rate limits, server errors and gateway timeouts are worth retrying.
+pub fn is_retryable_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Rate limits and unhealthy nodes reported in a JSON-RPC error object, which
+/// some providers send with HTTP 200, are worth retrying, as are batches the
+/// provider only partly answered.
+pub fn is_retryable_rpc_error(error: &RpcError) -> bool {
+    matches!(
+        error.kind,
+        RpcErrorKind::RateLimited { .. }
+            | RpcErrorKind::NodeUnhealthy { .. }
+            | RpcErrorKind::IncompleteBatch { .. }
+    )
+}
+
+/// Connection failures and timeouts are worth retrying.
+pub fn is_retryable_transport_error(error: &reqwest::Error) -> bool {
+    error.is_timeout() || error.is_connect()
+}
+
+/// Reads a `Retry-After` header, given in seconds or as an HTTP date.
+pub fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?;
+    parse_retry_after(value, Utc::now())
+}
+
+/// Parses a `Retry-After` value at `now`. A date that has already passed
+/// means retrying right away.
+fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&Utc) - now)
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+fn is_retryable_client_error(error: &ClientError) -> bool {
+    match error.kind() {
+        ClientErrorKind::Io(_) => true,
+        ClientErrorKind::Reqwest(error) => {
+            is_retryable_transport_error(error) || error.status().is_some_and(is_retryable_status)
+        }
+        _ => false,
+    }
+}
+
+/// Runs a `solana_client` call under `policy`, retrying transport failures,
+/// rate limits and server errors.
+pub async fn retry_client_call<T, F, Fut>(
+    policy: &RetryPolicy,
+    limiter: &RateLimiter,
+    mut call: F,
+) -> Result<T, ClientError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, ClientError>>,
+{
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        limiter.acquire().await;
+        match call().await {
+            Err(error) if attempt < policy.max_attempts && is_retryable_client_error(&error) => {
+                let delay = policy.backoff(attempt);
+                warn!(
+                    "RPC call failed ({}), retrying in {:?} (attempt {}/{})",
+                    error, delay, attempt, policy.max_attempts
+                );
+                tokio::time::sleep(delay).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::RpcErrorObject;
+
+    fn rpc_error(code: i64) -> RpcError {
+        RpcError::from_object(
+            "getTransaction",
+            RpcErrorObject {
+                code,
+                message: String::new(),
+                data: None,
+            },
+        )
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap_with_jitter() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(350),
+            ..RetryPolicy::default()
+        };
+        for (attempt, full) in [(1, 100), (2, 200), (3, 350), (10, 350)] {
+            let delay = policy.backoff(attempt).as_millis();
+            assert!((full / 2..=full).contains(&delay), "attempt {}", attempt);
+        }
+    }
+
+    #[test]
+    fn classifies_retryable_responses() {
+        assert!(is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable_status(StatusCode::BAD_GATEWAY));
+        assert!(!is_retryable_status(StatusCode::BAD_REQUEST));
+
+        assert!(is_retryable_rpc_error(&rpc_error(-32429)));
+        assert!(is_retryable_rpc_error(&rpc_error(-32005)));
+        assert!(!is_retryable_rpc_error(&rpc_error(-32009)));
+        assert!(!is_retryable_rpc_error(&rpc_error(-32602)));
+    }
+
+    #[test]
+    fn parses_retry_after_seconds_and_dates() {
+        let now = DateTime::parse_from_rfc3339("2015-10-21T07:28:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(parse_retry_after(" 7 ", now), Some(Duration::from_secs(7)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:27:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+}
@@ -0,0 +1,884 @@
+use log::{debug, warn};
+use reqwest::{Client, StatusCode};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use solana_sdk::commitment_config::CommitmentConfig;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+use crate::models::{RpcResponse, SolanaSignature};
+use crate::retry::{
+    is_retryable_rpc_error, is_retryable_status, is_retryable_transport_error, retry_after,
+    RateLimiter, RetryPolicy,
+};
+use crate::rpc_error::{RpcError, RpcErrorKind};
+
+/// The subset of the Solana JSON-RPC API used by the history commands.
+///
+/// Implementations can use `async fn`; the returned futures must be `Send`
+/// so callers can drive them from multi-threaded runtimes.
+pub trait SolanaRpc: Sync {
+    /// Fetches one page of `getSignaturesForAddress`, newest first.
+    fn get_signatures(
+        &self,
+        address: &str,
+        before: Option<&str>,
+        until: Option<&str>,
+        limit: usize,
+    ) -> impl Future<Output = Result<Vec<SolanaSignature>>> + Send;
+
+    /// Fetches a transaction, or `None` if the node doesn't know it. Results
+    /// are `jsonParsed` unless the implementation says otherwise; see
+    /// `transaction::parse_transaction`.
+    fn get_transaction(
+        &self,
+        signature: &str,
+    ) -> impl Future<Output = Result<Option<Value>>> + Send;
+
+    /// Fetches several transactions at once, in the order of `signatures`.
+    fn get_transactions(
+        &self,
+        signatures: &[String],
+    ) -> impl Future<Output = Result<Vec<Option<Value>>>> + Send {
+        async move {
+            let mut transactions = Vec::with_capacity(signatures.len());
+            for signature in signatures {
+                transactions.push(self.get_transaction(signature).await?);
+            }
+            Ok(transactions)
+        }
+    }
+
+    /// Fetches the production time of a block, or `None` if it isn't available.
+    fn get_block_time(&self, slot: u64) -> impl Future<Output = Result<Option<i64>>> + Send;
+
+    /// Fetches a `jsonParsed` account, or `None` if it doesn't exist.
+    fn get_account(&self, pubkey: &str) -> impl Future<Output = Result<Option<Value>>> + Send;
+
+    /// Fetches several `jsonParsed` accounts at once, in the order of `pubkeys`.
+    fn get_multiple_accounts(
+        &self,
+        pubkeys: &[String],
+    ) -> impl Future<Output = Result<Vec<Option<Value>>>> + Send {
+        async move {
+            let mut accounts = Vec::with_capacity(pubkeys.len());
+            for pubkey in pubkeys {
+                accounts.push(self.get_account(pubkey).await?);
+            }
+            Ok(accounts)
+        }
+    }
+
+    /// Fetches the `jsonParsed` token accounts of `owner` held under the
+    /// token program `program_id`, as `{ "pubkey", "account" }` objects.
+    fn get_token_accounts_by_owner(
+        &self,
+        owner: &str,
+        program_id: &str,
+    ) -> impl Future<Output = Result<Vec<Value>>> + Send;
+}
+
+/// Largest number of accounts `getMultipleAccounts` accepts in one call.
+const MAX_ACCOUNTS_PER_CALL: usize = 100;
+
+/// Newest transaction version the history commands understand. Without it
+/// nodes refuse to return v0 transactions.
+const MAX_SUPPORTED_TRANSACTION_VERSION: u8 = 0;
+
+/// Encodings `getTransaction` results can be requested in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TransactionEncoding {
+    /// Decoded by the node. Programs it doesn't know are left raw, and some
+    /// providers don't support it consistently.
+    #[default]
+    JsonParsed,
+    /// Raw transaction bytes, decoded locally by `decode::decode_transaction`.
+    Base64,
+}
+
+impl TransactionEncoding {
+    fn as_str(self) -> &'static str {
+        match self {
+            TransactionEncoding::JsonParsed => "jsonParsed",
+            TransactionEncoding::Base64 => "base64",
+        }
+    }
+}
+
+/// `getTransaction` params for `signature` in `encoding`.
+fn transaction_params(
+    signature: &str,
+    commitment: CommitmentConfig,
+    encoding: TransactionEncoding,
+) -> Value {
+    serde_json::json!([
+        signature,
+        {
+            "encoding": encoding.as_str(),
+            "maxSupportedTransactionVersion": MAX_SUPPORTED_TRANSACTION_VERSION,
+            "commitment": commitment.commitment
+        }
+    ])
+}
+
+/// `SolanaRpc` over HTTP JSON-RPC. Every request goes through the client's
+/// `RetryPolicy` and request budget, and reads at its commitment level.
+pub struct HttpRpc {
+    client: Client,
+    url: String,
+    commitment: CommitmentConfig,
+    transaction_encoding: TransactionEncoding,
+    retry_policy: RetryPolicy,
+    rate_limiter: RateLimiter,
+    /// Cleared once the provider rejects a batch request, so later calls go
+    /// straight to single requests.
+    batches_supported: AtomicBool,
+}
+
+impl HttpRpc {
+    pub fn new(url: &str, retry_policy: RetryPolicy, commitment: CommitmentConfig) -> Self {
+        HttpRpc {
+            client: Client::new(),
+            url: url.to_string(),
+            commitment,
+            transaction_encoding: TransactionEncoding::default(),
+            rate_limiter: RateLimiter::new(retry_policy.requests_per_second),
+            retry_policy,
+            batches_supported: AtomicBool::new(true),
+        }
+    }
+
+    /// Requests `getTransaction` results in `encoding` instead of
+    /// `jsonParsed`.
+    pub fn with_transaction_encoding(mut self, encoding: TransactionEncoding) -> Self {
+        self.transaction_encoding = encoding;
+        self
+    }
+
+    /// Commitment for methods that don't accept `processed`
+    /// (`getSignaturesForAddress`, `getTransaction`), which fall back to
+    /// `confirmed`.
+    fn history_commitment(&self) -> CommitmentConfig {
+        if self.commitment.is_processed() {
+            CommitmentConfig::confirmed()
+        } else {
+            self.commitment
+        }
+    }
+
+    /// Posts `payload`, retrying transport failures, rate limits and server
+    /// errors with backoff. A `Retry-After` header takes precedence over the
+    /// computed delay. `attempt` counts the requests already made for the
+    /// call and is shared with retries of JSON-RPC error objects, so a call
+    /// makes at most `max_attempts` requests in all.
+    async fn post(&self, payload: &Value, attempt: &mut u32) -> Result<PostResponse> {
+        let max_attempts = self.retry_policy.max_attempts;
+        loop {
+            *attempt += 1;
+            self.rate_limiter.acquire().await;
+
+            let (reason, delay) = match self.client.post(&self.url).json(payload).send().await {
+                Ok(response) => {
+                    let status = response.status();
+                    let retry_after = retry_after(&response);
+                    if !is_retryable_status(status) || *attempt >= max_attempts {
+                        return Ok(PostResponse {
+                            status,
+                            retry_after,
+                            body: response.text().await?,
+                        });
+                    }
+                    let delay = retry_after.unwrap_or_else(|| self.retry_policy.backoff(*attempt));
+                    (format!("HTTP {}", status), delay)
+                }
+                Err(error) if is_retryable_transport_error(&error) && *attempt < max_attempts => {
+                    (error.to_string(), self.retry_policy.backoff(*attempt))
+                }
+                Err(error) => return Err(error.into()),
+            };
+
+            warn!(
+                "Request to {} failed ({}), retrying in {:?} (attempt {}/{})",
+                self.url, reason, delay, attempt, max_attempts
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Sends `params_list` as one JSON-RPC batch of `method` calls and returns
+    /// each call's `result` or error in request order. Returns `Ok(None)` if
+    /// the provider doesn't accept batch requests.
+    async fn call_batch(
+        &self,
+        method: &str,
+        params_list: Vec<Value>,
+    ) -> Result<Option<Vec<std::result::Result<Value, RpcError>>>> {
+        let count = params_list.len();
+        let payload: Value = params_list
+            .into_iter()
+            .enumerate()
+            .map(|(id, params)| {
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "method": method,
+                    "params": params
+                })
+            })
+            .collect();
+
+        let mut attempt = 0;
+        'attempts: loop {
+            debug!(
+                "Sending batch of {} {} requests to {}",
+                count, method, self.url
+            );
+            let PostResponse {
+                status,
+                retry_after,
+                body: response_text,
+            } = self.post(&payload, &mut attempt).await?;
+            if is_retryable_status(status) {
+                return Err(RpcError::from_status(method, status, response_text).into());
+            }
+            if !status.is_success() {
+                debug!("Batch rejected with HTTP {}: {}", status, response_text);
+                return Ok(None);
+            }
+
+            // Providers without batch support answer with a single error object.
+            let responses: Vec<Value> = match serde_json::from_str(&response_text)? {
+                Value::Array(responses) => responses,
+                other => {
+                    debug!("Batch rejected: {}", other);
+                    return Ok(None);
+                }
+            };
+
+            // Batch responses may arrive in any order, so match them up by id.
+            let mut results: Vec<Option<std::result::Result<Value, RpcError>>> =
+                (0..count).map(|_| None).collect();
+            for response in responses {
+                let response: RpcResponse<Value> = serde_json::from_value(response)?;
+                let id = response
+                    .id
+                    .map(|id| id as usize)
+                    .filter(|id| *id < count)
+                    .ok_or_else(|| Error::parse("batch response with missing or unknown id"))?;
+                results[id] = Some(match response.error {
+                    Some(error) => {
+                        let error = RpcError::from_object(method, error);
+                        if is_retryable_rpc_error(&error) {
+                            // One rate-limited call fails the batch; resend all of it.
+                            self.wait_to_retry(error, retry_after, attempt).await?;
+                            continue 'attempts;
+                        }
+                        Err(error)
+                    }
+                    None => Ok(response.result.unwrap_or_default()),
+                });
+            }
+
+            // Calls the provider dropped from its answer would otherwise be
+            // lost, so resend the batch.
+            let missing = results.iter().filter(|result| result.is_none()).count();
+            if missing > 0 {
+                let kind = RpcErrorKind::IncompleteBatch { missing, count };
+                self.wait_to_retry(RpcError::new(method, kind), retry_after, attempt)
+                    .await?;
+                continue;
+            }
+            return Ok(Some(results.into_iter().flatten().collect()));
+        }
+    }
+
+    /// Sends a single JSON-RPC request and deserializes its `result`.
+    async fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T> {
+        let payload = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": params
+        });
+
+        let mut attempt = 0;
+        loop {
+            debug!("Sending {} request to {}", method, self.url);
+            let PostResponse {
+                status,
+                retry_after,
+                body: response_text,
+            } = self.post(&payload, &mut attempt).await?;
+            if !status.is_success() {
+                return Err(RpcError::from_status(method, status, response_text).into());
+            }
+
+            // Deserialize the result separately so that a `null` result still
+            // works for methods returning an `Option`.
+            let rpc_response: RpcResponse<Value> = serde_json::from_str(&response_text)?;
+            match rpc_response.error {
+                Some(error) => {
+                    self.wait_to_retry(RpcError::from_object(method, error), retry_after, attempt)
+                        .await?
+                }
+                None => {
+                    return Ok(serde_json::from_value(
+                        rpc_response.result.unwrap_or_default(),
+                    )?)
+                }
+            }
+        }
+    }
+
+    /// Waits out the delay after attempt number `attempt` failed with a
+    /// JSON-RPC error object: the response's `Retry-After` if it had one,
+    /// the policy's backoff otherwise. Returns the error instead if it isn't
+    /// worth retrying or the policy's attempts are used up.
+    async fn wait_to_retry(
+        &self,
+        error: RpcError,
+        retry_after: Option<Duration>,
+        attempt: u32,
+    ) -> Result<()> {
+        let max_attempts = self.retry_policy.max_attempts;
+        if !is_retryable_rpc_error(&error) || attempt >= max_attempts {
+            return Err(error.into());
+        }
+
+        let delay = retry_after.unwrap_or_else(|| self.retry_policy.backoff(attempt));
+        warn!(
+            "Request to {} failed ({}), retrying in {:?} (attempt {}/{})",
+            self.url, error, delay, attempt, max_attempts
+        );
+        tokio::time::sleep(delay).await;
+        Ok(())
+    }
+}
+
+/// The response `post` settled on.
+struct PostResponse {
+    status: StatusCode,
+    /// Delay the server asked for before the next attempt.
+    retry_after: Option<Duration>,
+    body: String,
+}
+
+impl SolanaRpc for HttpRpc {
+    async fn get_signatures(
+        &self,
+        address: &str,
+        before: Option<&str>,
+        until: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<SolanaSignature>> {
+        let mut config = serde_json::json!({
+            "limit": limit,
+            "commitment": self.history_commitment().commitment
+        });
+        if let Some(before) = before {
+            config["before"] = serde_json::json!(before);
+        }
+        if let Some(until) = until {
+            config["until"] = serde_json::json!(until);
+        }
+        self.call(
+            "getSignaturesForAddress",
+            serde_json::json!([address, config]),
+        )
+        .await
+    }
+
+    async fn get_transaction(&self, signature: &str) -> Result<Option<Value>> {
+        let result = self
+            .call(
+                "getTransaction",
+                transaction_params(
+                    signature,
+                    self.history_commitment(),
+                    self.transaction_encoding,
+                ),
+            )
+            .await;
+        match result {
+            Err(Error::Rpc(error)) => unavailable_transaction(signature, error),
+            result => result,
+        }
+    }
+
+    async fn get_transactions(&self, signatures: &[String]) -> Result<Vec<Option<Value>>> {
+        if signatures.len() > 1 && self.batches_supported.load(Ordering::Relaxed) {
+            let params_list = signatures
+                .iter()
+                .map(|signature| {
+                    transaction_params(
+                        signature,
+                        self.history_commitment(),
+                        self.transaction_encoding,
+                    )
+                })
+                .collect();
+            match self.call_batch("getTransaction", params_list).await? {
+                Some(results) => {
+                    return signatures
+                        .iter()
+                        .zip(results)
+                        .map(|(signature, result)| match result {
+                            Ok(result) => Ok(Some(result).filter(|result| !result.is_null())),
+                            Err(error) => unavailable_transaction(signature, error),
+                        })
+                        .collect()
+                }
+                None => {
+                    warn!(
+                        "{} rejected a batch request, falling back to single requests",
+                        self.url
+                    );
+                    self.batches_supported.store(false, Ordering::Relaxed);
+                }
+            }
+        }
+
+        let mut transactions = Vec::with_capacity(signatures.len());
+        for signature in signatures {
+            transactions.push(self.get_transaction(signature).await?);
+        }
+        Ok(transactions)
+    }
+
+    async fn get_block_time(&self, slot: u64) -> Result<Option<i64>> {
+        self.call("getBlockTime", serde_json::json!([slot])).await
+    }
+
+    async fn get_account(&self, pubkey: &str) -> Result<Option<Value>> {
+        let response: Value = self
+            .call(
+                "getAccountInfo",
+                serde_json::json!([
+                    pubkey,
+                    {
+                        "encoding": "jsonParsed",
+                        "commitment": self.commitment.commitment
+                    }
+                ]),
+            )
+            .await?;
+        Ok(response
+            .get("value")
+            .filter(|value| !value.is_null())
+            .cloned())
+    }
+
+    async fn get_multiple_accounts(&self, pubkeys: &[String]) -> Result<Vec<Option<Value>>> {
+        let mut accounts = Vec::with_capacity(pubkeys.len());
+        for chunk in pubkeys.chunks(MAX_ACCOUNTS_PER_CALL) {
+            let response: Value = self
+                .call(
+                    "getMultipleAccounts",
+                    serde_json::json!([
+                        chunk,
+                        {
+                            "encoding": "jsonParsed",
+                            "commitment": self.commitment.commitment
+                        }
+                    ]),
+                )
+                .await?;
+            let values = response
+                .get("value")
+                .and_then(|v| v.as_array())
+                .filter(|values| values.len() == chunk.len())
+                .ok_or_else(|| Error::parse("getMultipleAccounts returned unexpected value"))?;
+            accounts.extend(
+                values
+                    .iter()
+                    .map(|value| Some(value.clone()).filter(|value| !value.is_null())),
+            );
+        }
+        Ok(accounts)
+    }
+
+    async fn get_token_accounts_by_owner(
+        &self,
+        owner: &str,
+        program_id: &str,
+    ) -> Result<Vec<Value>> {
+        let response: Value = self
+            .call(
+                "getTokenAccountsByOwner",
+                serde_json::json!([
+                    owner,
+                    { "programId": program_id },
+                    {
+                        "encoding": "jsonParsed",
+                        "commitment": self.commitment.commitment
+                    }
+                ]),
+            )
+            .await?;
+        response
+            .get("value")
+            .and_then(|v| v.as_array())
+            .cloned()
+            .ok_or_else(|| Error::parse("getTokenAccountsByOwner returned unexpected value"))
+    }
+}
+
+/// Treats a `getTransaction` error for a transaction the node can't serve
+/// like a transaction it doesn't know, so one pruned or skipped slot doesn't
+/// fail a whole history. Other errors are returned.
+fn unavailable_transaction(signature: &str, error: RpcError) -> Result<Option<Value>> {
+    if !error.is_unavailable() {
+        return Err(error.into());
+    }
+    warn!("Transaction {} not available: {}", signature, error);
+    Ok(None)
+}
+
+/// `SolanaRpc` backed by in-memory fixtures, for running the history
+/// commands without a network.
+#[derive(Debug, Default)]
+pub struct MemoryRpc {
+    /// Signatures per address, newest first.
+    signatures: HashMap<String, Vec<SolanaSignature>>,
+    transactions: HashMap<String, Value>,
+    block_times: HashMap<u64, i64>,
+    accounts: HashMap<String, Value>,
+    /// Keyed token accounts per owner.
+    token_accounts: HashMap<String, Vec<Value>>,
+}
+
+impl MemoryRpc {
+    pub fn new() -> Self {
+        MemoryRpc::default()
+    }
+
+    /// Records `signature` for `address`. Signatures must be added newest first.
+    pub fn add_signature(&mut self, address: &str, signature: SolanaSignature) {
+        self.signatures
+            .entry(address.to_string())
+            .or_default()
+            .push(signature);
+    }
+
+    /// Records the `getTransaction` result for `signature`.
+    pub fn add_transaction(&mut self, signature: &str, transaction: Value) {
+        self.transactions.insert(signature.to_string(), transaction);
+    }
+
+    pub fn add_block_time(&mut self, slot: u64, block_time: i64) {
+        self.block_times.insert(slot, block_time);
+    }
+
+    /// Records the `getAccountInfo` value for `pubkey`.
+    pub fn add_account(&mut self, pubkey: &str, account: Value) {
+        self.accounts.insert(pubkey.to_string(), account);
+    }
+
+    /// Records a `getTokenAccountsByOwner` entry (`{ "pubkey", "account" }`)
+    /// for `owner`. The token program is read from `account.owner`.
+    pub fn add_token_account(&mut self, owner: &str, keyed_account: Value) {
+        self.token_accounts
+            .entry(owner.to_string())
+            .or_default()
+            .push(keyed_account);
+    }
+}
+
+impl SolanaRpc for MemoryRpc {
+    async fn get_signatures(
+        &self,
+        address: &str,
+        before: Option<&str>,
+        until: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<SolanaSignature>> {
+        let all = match self.signatures.get(address) {
+            Some(all) => all,
+            None => return Ok(Vec::new()),
+        };
+
+        let start = match before {
+            Some(before) => match all.iter().position(|s| s.signature == before) {
+                Some(index) => index + 1,
+                None => return Ok(Vec::new()),
+            },
+            None => 0,
+        };
+
+        Ok(all[start..]
+            .iter()
+            .take_while(|s| Some(s.signature.as_str()) != until)
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_transaction(&self, signature: &str) -> Result<Option<Value>> {
+        Ok(self.transactions.get(signature).cloned())
+    }
+
+    async fn get_block_time(&self, slot: u64) -> Result<Option<i64>> {
+        Ok(self.block_times.get(&slot).copied())
+    }
+
+    async fn get_account(&self, pubkey: &str) -> Result<Option<Value>> {
+        Ok(self.accounts.get(pubkey).cloned())
+    }
+
+    async fn get_token_accounts_by_owner(
+        &self,
+        owner: &str,
+        program_id: &str,
+    ) -> Result<Vec<Value>> {
+        Ok(self
+            .token_accounts
+            .get(owner)
+            .into_iter()
+            .flatten()
+            .filter(|keyed| {
+                keyed.pointer("/account/owner").and_then(|v| v.as_str()) == Some(program_id)
+            })
+            .cloned()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// An HTTP response with `status`, extra `headers` lines and `body`.
+    fn http(status: &str, headers: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {}\r\n{}Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            headers,
+            body.len(),
+            body
+        )
+    }
+
+    /// An HTTP 200 response carrying `body`.
+    fn ok(body: Value) -> String {
+        http("200 OK", "", &body.to_string())
+    }
+
+    /// Serves `responses` in order, one per request, repeating the last one
+    /// once they run out. Returns the URL and a count of requests served.
+    async fn serve(responses: Vec<String>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let served = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0; 4096];
+                // Read the headers, then as much body as they announce.
+                let body_start = loop {
+                    let read = stream.read(&mut buffer).await.unwrap();
+                    request.extend_from_slice(&buffer[..read]);
+                    if let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                        break end + 4;
+                    }
+                };
+                let headers = String::from_utf8_lossy(&request[..body_start]).to_lowercase();
+                let length: usize = headers
+                    .lines()
+                    .find_map(|line| line.strip_prefix("content-length:"))
+                    .map_or(0, |length| length.trim().parse().unwrap());
+                while request.len() < body_start + length {
+                    let read = stream.read(&mut buffer).await.unwrap();
+                    request.extend_from_slice(&buffer[..read]);
+                }
+
+                let index = served.fetch_add(1, Ordering::SeqCst);
+                let response = &responses[index.min(responses.len() - 1)];
+                stream.write_all(response.as_bytes()).await.unwrap();
+                stream.shutdown().await.unwrap();
+            }
+        });
+        (url, requests)
+    }
+
+    fn rpc(url: &str) -> HttpRpc {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            ..RetryPolicy::default()
+        };
+        HttpRpc::new(url, policy, CommitmentConfig::finalized())
+    }
+
+    fn error(id: u64, code: i64) -> Value {
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": code, "message": "unavailable" }
+        })
+    }
+
+    fn result(id: u64, result: Value) -> Value {
+        serde_json::json!({ "jsonrpc": "2.0", "id": id, "result": result })
+    }
+
+    fn signatures(count: usize) -> Vec<String> {
+        (0..count).map(|index| format!("sig{}", index)).collect()
+    }
+
+    #[tokio::test]
+    async fn batch_keeps_results_around_unavailable_transactions() {
+        let (url, _) = serve(vec![ok(serde_json::json!([
+            result(0, Value::Null),
+            error(1, -32009),
+            result(2, serde_json::json!({ "slot": 3 })),
+            error(3, -32004),
+        ]))])
+        .await;
+
+        let transactions = rpc(&url).get_transactions(&signatures(4)).await.unwrap();
+        assert_eq!(
+            transactions,
+            [None, None, Some(serde_json::json!({ "slot": 3 })), None]
+        );
+    }
+
+    #[tokio::test]
+    async fn batch_fails_on_other_errors() {
+        let (url, _) = serve(vec![ok(serde_json::json!([
+            result(0, Value::Null),
+            error(1, -32602),
+        ]))])
+        .await;
+
+        let error = rpc(&url)
+            .get_transactions(&signatures(2))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Rpc(RpcError {
+                kind: RpcErrorKind::InvalidRequest { .. },
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn single_request_skips_unavailable_transaction() {
+        let (url, _) = serve(vec![ok(error(1, -32007))]).await;
+        assert_eq!(rpc(&url).get_transaction("sig0").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn incomplete_batch_is_resent() {
+        let (url, requests) = serve(vec![
+            ok(serde_json::json!([result(
+                1,
+                serde_json::json!({ "slot": 2 })
+            )])),
+            ok(serde_json::json!([
+                result(1, serde_json::json!({ "slot": 2 })),
+                result(0, serde_json::json!({ "slot": 1 })),
+            ])),
+        ])
+        .await;
+
+        let transactions = rpc(&url).get_transactions(&signatures(2)).await.unwrap();
+        assert_eq!(
+            transactions,
+            [
+                Some(serde_json::json!({ "slot": 1 })),
+                Some(serde_json::json!({ "slot": 2 })),
+            ]
+        );
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn incomplete_batch_fails_once_attempts_run_out() {
+        let (url, requests) = serve(vec![ok(serde_json::json!([result(0, Value::Null)]))]).await;
+
+        let error = rpc(&url)
+            .get_transactions(&signatures(2))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Rpc(RpcError {
+                kind: RpcErrorKind::IncompleteBatch {
+                    missing: 1,
+                    count: 2
+                },
+                ..
+            })
+        ));
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn error_objects_and_http_failures_share_the_attempt_budget() {
+        let (url, requests) = serve(vec![
+            http("503 Service Unavailable", "", ""),
+            ok(error(1, -32429)),
+            ok(result(1, serde_json::json!(1700000000))),
+        ])
+        .await;
+        let rpc = HttpRpc::new(
+            &url,
+            RetryPolicy {
+                max_attempts: 2,
+                base_delay: Duration::from_millis(1),
+                ..RetryPolicy::default()
+            },
+            CommitmentConfig::finalized(),
+        );
+
+        let error = rpc.get_block_time(1).await.unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Rpc(RpcError {
+                kind: RpcErrorKind::RateLimited { .. },
+                ..
+            })
+        ));
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn rate_limit_objects_wait_for_retry_after() {
+        let (url, requests) = serve(vec![
+            http(
+                "200 OK",
+                "Retry-After: 0\r\n",
+                &error(1, -32429).to_string(),
+            ),
+            ok(result(1, serde_json::json!(1700000000))),
+        ])
+        .await;
+        // Without the header the first retry would wait at least 30 seconds.
+        let rpc = HttpRpc::new(
+            &url,
+            RetryPolicy {
+                base_delay: Duration::from_secs(60),
+                ..RetryPolicy::default()
+            },
+            CommitmentConfig::finalized(),
+        );
+
+        let block_time = tokio::time::timeout(Duration::from_secs(10), rpc.get_block_time(1))
+            .await
+            .expect("Retry-After was ignored")
+            .unwrap();
+        assert_eq!(block_time, Some(1700000000));
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,236 @@
+use reqwest::StatusCode;
+use serde_json::Value;
+use std::fmt;
+
+use crate::models::RpcErrorObject;
+
+/// A failed JSON-RPC call, tagged with the method that failed.
+#[derive(Debug)]
+pub struct RpcError {
+    pub method: String,
+    pub kind: RpcErrorKind,
+}
+
+/// Why a JSON-RPC call failed. Well-known Solana error codes get their own
+/// variant; everything else is kept as `Other`.
+#[derive(Debug)]
+pub enum RpcErrorKind {
+    /// The node answered with a non-success HTTP status.
+    Http { status: StatusCode, body: String },
+    /// The provider's rate limit was still exceeded after retrying.
+    RateLimited { message: String },
+    /// The slot was skipped or is missing from long-term storage
+    /// (-32007, -32009).
+    SlotSkipped { message: String },
+    /// The block isn't available on this node, cleaned up or not yet
+    /// confirmed (-32001, -32004, -32014).
+    BlockNotAvailable { message: String },
+    /// The node is unhealthy or behind the requested slot (-32005, -32016).
+    NodeUnhealthy { message: String },
+    /// The node doesn't keep transaction history (-32011).
+    HistoryNotAvailable { message: String },
+    /// The transaction version is newer than requested (-32015).
+    UnsupportedTransactionVersion { message: String },
+    /// The request itself was rejected (-32600, -32601, -32602).
+    InvalidRequest { code: i64, message: String },
+    /// The provider left `missing` of `count` batched calls unanswered.
+    IncompleteBatch { missing: usize, count: usize },
+    /// Any other JSON-RPC error object.
+    Other {
+        code: i64,
+        message: String,
+        data: Option<Value>,
+    },
+}
+
+impl RpcError {
+    pub fn new(method: &str, kind: RpcErrorKind) -> Self {
+        RpcError {
+            method: method.to_string(),
+            kind,
+        }
+    }
+
+    /// Maps a JSON-RPC `error` object returned for `method`.
+    pub fn from_object(method: &str, error: RpcErrorObject) -> Self {
+        let RpcErrorObject {
+            code,
+            message,
+            data,
+        } = error;
+        let kind = match code {
+            429 | -32429 => RpcErrorKind::RateLimited { message },
+            -32007 | -32009 => RpcErrorKind::SlotSkipped { message },
+            -32001 | -32004 | -32014 => RpcErrorKind::BlockNotAvailable { message },
+            -32005 | -32016 => RpcErrorKind::NodeUnhealthy { message },
+            -32011 => RpcErrorKind::HistoryNotAvailable { message },
+            -32015 => RpcErrorKind::UnsupportedTransactionVersion { message },
+            -32602..=-32600 => RpcErrorKind::InvalidRequest { code, message },
+            _ => RpcErrorKind::Other {
+                code,
+                message,
+                data,
+            },
+        };
+        RpcError::new(method, kind)
+    }
+
+    /// Whether the node can't serve the requested block or transaction:
+    /// its slot was skipped, it was cleaned up, or the node keeps no
+    /// history. Other nodes may still have it.
+    pub fn is_unavailable(&self) -> bool {
+        matches!(
+            self.kind,
+            RpcErrorKind::SlotSkipped { .. }
+                | RpcErrorKind::BlockNotAvailable { .. }
+                | RpcErrorKind::HistoryNotAvailable { .. }
+        )
+    }
+
+    /// Maps a non-success HTTP response to `method`.
+    pub fn from_status(method: &str, status: StatusCode, body: String) -> Self {
+        let kind = if status == StatusCode::TOO_MANY_REQUESTS {
+            RpcErrorKind::RateLimited { message: body }
+        } else {
+            RpcErrorKind::Http { status, body }
+        };
+        RpcError::new(method, kind)
+    }
+}
+
+impl fmt::Display for RpcErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RpcErrorKind::Http { status, body } => write!(f, "HTTP {}: {}", status, body),
+            RpcErrorKind::RateLimited { message } => write!(f, "rate limited: {}", message),
+            RpcErrorKind::SlotSkipped { message } => write!(f, "slot skipped: {}", message),
+            RpcErrorKind::BlockNotAvailable { message } => {
+                write!(f, "block not available: {}", message)
+            }
+            RpcErrorKind::NodeUnhealthy { message } => write!(f, "node unhealthy: {}", message),
+            RpcErrorKind::HistoryNotAvailable { message } => {
+                write!(f, "transaction history not available: {}", message)
+            }
+            RpcErrorKind::UnsupportedTransactionVersion { message } => {
+                write!(f, "unsupported transaction version: {}", message)
+            }
+            RpcErrorKind::InvalidRequest { code, message } => {
+                write!(f, "invalid request ({}): {}", code, message)
+            }
+            RpcErrorKind::IncompleteBatch { missing, count } => {
+                write!(f, "{} of {} batched calls unanswered", missing, count)
+            }
+            RpcErrorKind::Other {
+                code,
+                message,
+                data,
+            } => {
+                write!(f, "error {}: {}", code, message)?;
+                if let Some(data) = data {
+                    write!(f, " ({})", data)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} failed: {}", self.method, self.kind)
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_code(code: i64) -> RpcErrorKind {
+        let error = RpcErrorObject {
+            code,
+            message: "message".to_string(),
+            data: Some(serde_json::json!({ "slot": 7 })),
+        };
+        RpcError::from_object("getTransaction", error).kind
+    }
+
+    #[test]
+    fn maps_well_known_error_codes() {
+        assert!(matches!(
+            from_code(-32429),
+            RpcErrorKind::RateLimited { .. }
+        ));
+        assert!(matches!(from_code(429), RpcErrorKind::RateLimited { .. }));
+        for code in [-32007, -32009] {
+            assert!(matches!(from_code(code), RpcErrorKind::SlotSkipped { .. }));
+        }
+        for code in [-32001, -32004, -32014] {
+            assert!(matches!(
+                from_code(code),
+                RpcErrorKind::BlockNotAvailable { .. }
+            ));
+        }
+        for code in [-32005, -32016] {
+            assert!(matches!(
+                from_code(code),
+                RpcErrorKind::NodeUnhealthy { .. }
+            ));
+        }
+        assert!(matches!(
+            from_code(-32011),
+            RpcErrorKind::HistoryNotAvailable { .. }
+        ));
+        assert!(matches!(
+            from_code(-32015),
+            RpcErrorKind::UnsupportedTransactionVersion { .. }
+        ));
+        assert!(matches!(
+            from_code(-32601),
+            RpcErrorKind::InvalidRequest { code: -32601, .. }
+        ));
+    }
+
+    #[test]
+    fn keeps_unknown_errors_with_their_data() {
+        let error = RpcError::from_object(
+            "getBlockTime",
+            RpcErrorObject {
+                code: -32099,
+                message: "custom".to_string(),
+                data: Some(serde_json::json!({ "slot": 7 })),
+            },
+        );
+        assert!(matches!(
+            &error.kind,
+            RpcErrorKind::Other {
+                code: -32099,
+                data: Some(_),
+                ..
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            r#"getBlockTime failed: error -32099: custom ({"slot":7})"#
+        );
+    }
+
+    #[test]
+    fn maps_http_statuses() {
+        let error = RpcError::from_status(
+            "getBlockTime",
+            StatusCode::TOO_MANY_REQUESTS,
+            "slow down".into(),
+        );
+        assert!(matches!(error.kind, RpcErrorKind::RateLimited { .. }));
+        let error = RpcError::from_status("getBlockTime", StatusCode::BAD_GATEWAY, String::new());
+        assert!(matches!(
+            error.kind,
+            RpcErrorKind::Http {
+                status: StatusCode::BAD_GATEWAY,
+                ..
+            }
+        ));
+    }
+}
@@ -0,0 +1,112 @@
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    system_instruction,
+    transaction::Transaction,
+};
+use spl_associated_token_account::{
+    get_associated_token_address, instruction::create_associated_token_account,
+};
+use spl_token::instruction::transfer;
+
+use crate::error::Result;
+use crate::retry::{retry_client_call, RateLimiter, RetryPolicy};
+
+/// Builds, signs and submits transfers, retrying every RPC call under a
+/// `RetryPolicy`.
+pub struct SendClient {
+    rpc_client: RpcClient,
+    retry_policy: RetryPolicy,
+    rate_limiter: RateLimiter,
+}
+
+impl SendClient {
+    pub fn new(url: &str, commitment: CommitmentConfig, retry_policy: RetryPolicy) -> Self {
+        SendClient {
+            rpc_client: RpcClient::new_with_commitment(url.to_string(), commitment),
+            rate_limiter: RateLimiter::new(retry_policy.requests_per_second),
+            retry_policy,
+        }
+    }
+
+    /// Transfers `lamports` from `sender` to `recipient` and waits for the
+    /// client's commitment level.
+    pub async fn send_sol(
+        &self,
+        sender: &Keypair,
+        recipient: &Pubkey,
+        lamports: u64,
+    ) -> Result<Signature> {
+        let transfer_instruction =
+            system_instruction::transfer(&sender.pubkey(), recipient, lamports);
+        self.sign_and_send(sender, &[transfer_instruction]).await
+    }
+
+    /// Transfers `amount` base units of `mint` from the sender's associated
+    /// token account to the recipient's, creating the recipient's account
+    /// first if it doesn't exist.
+    pub async fn send_fungible(
+        &self,
+        sender: &Keypair,
+        recipient: &Pubkey,
+        mint: &Pubkey,
+        amount: u64,
+    ) -> Result<Signature> {
+        let token_program_id = spl_token_2022::id();
+        let sender_token_account = get_associated_token_address(&sender.pubkey(), mint);
+        let recipient_token_account = get_associated_token_address(recipient, mint);
+
+        // Create associated token account for recipient if it doesn't exist
+        let mut instructions = vec![];
+        let recipient_account = retry_client_call(&self.retry_policy, &self.rate_limiter, || {
+            self.rpc_client
+                .get_account_with_commitment(&recipient_token_account, self.rpc_client.commitment())
+        })
+        .await?
+        .value;
+        if recipient_account.is_none() {
+            let create_account_instruction = create_associated_token_account(
+                &sender.pubkey(),
+                recipient,
+                mint,
+                &token_program_id,
+            );
+            instructions.push(create_account_instruction);
+        }
+
+        // Transfer instruction
+        let transfer_instruction = transfer(
+            &token_program_id,
+            &sender_token_account,
+            &recipient_token_account,
+            &sender.pubkey(),
+            &[&sender.pubkey()],
+            amount,
+        )?;
+        instructions.push(transfer_instruction);
+
+        self.sign_and_send(sender, &instructions).await
+    }
+
+    async fn sign_and_send(
+        &self,
+        payer: &Keypair,
+        instructions: &[Instruction],
+    ) -> Result<Signature> {
+        let recent_blockhash = retry_client_call(&self.retry_policy, &self.rate_limiter, || {
+            self.rpc_client.get_latest_blockhash()
+        })
+        .await?;
+        let mut transaction = Transaction::new_with_payer(instructions, Some(&payer.pubkey()));
+        transaction.try_sign(&[payer], recent_blockhash)?;
+
+        let signature = retry_client_call(&self.retry_policy, &self.rate_limiter, || {
+            self.rpc_client.send_and_confirm_transaction(&transaction)
+        })
+        .await?;
+        Ok(signature)
+    }
+}
@@ -1,101 +1,246 @@
-use crate::models::RpcResponse;
-use crate::models::SolanaSignature;
+use crate::balance_delta::SolBalanceDelta;
+use crate::error::Result;
+use crate::history::{
+    fetch_history, parse_history_transaction, FetchOptions, ParsedHistory, SignaturePaging,
+};
+use crate::rpc::SolanaRpc;
 use crate::sol_transfer::is_sol_transfer;
 use crate::sol_transfer::SolTransfer;
-use prettytable::{row, Table};
-use reqwest::blocking::Client;
-use serde_json::Value;
-use chrono::{TimeZone, Utc};
-
-/// Fetches and displays the SOL transaction history for a given Solana address.
-pub fn print_sol_transfer_history(
-    solana_rpc_url: &str,
+
+/// Fetches the SOL transfers in the transaction history of a given Solana
+/// address, newest transaction first and in instruction order within a
+/// transaction. Transactions that can't be parsed are returned in
+/// `skipped`.
+pub async fn fetch_sol_transfer_history(
+    rpc: &impl SolanaRpc,
     solana_address: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    println!(
-        "Print sol history for {} via {}",
-        solana_address, solana_rpc_url
-    );
-
-    // Create a client
-    let client = Client::new();
-
-    // Fetch the transaction signatures
-    let signatures_payload = serde_json::json!({
-        "jsonrpc": "2.0",
-        "id": 1,
-        "method": "getSignaturesForAddress",
-        "params": [
-            solana_address,
-            {
-                "limit": 10
+    paging: &SignaturePaging,
+    options: &FetchOptions,
+) -> Result<ParsedHistory<SolTransfer>> {
+    let mut history = ParsedHistory::default();
+
+    for (signature, json) in fetch_history(rpc, solana_address, paging, options).await? {
+        let transfers = parse_history_transaction(&signature, &json).and_then(|transaction| {
+            if is_sol_transfer(&transaction) {
+                SolTransfer::from_transaction(&transaction)
+            } else {
+                Ok(Vec::new())
             }
-        ]
-    });
-
-    let signatures_response = client
-        .post(solana_rpc_url)
-        .json(&signatures_payload)
-        .send()?;
-
-    if !signatures_response.status().is_success() {
-        eprintln!(
-            "Error: Received HTTP {} for transaction signatures request",
-            signatures_response.status()
-        );
-        if let Ok(error_text) = signatures_response.text() {
-            eprintln!("Response: {}", error_text);
+        });
+        match transfers {
+            Ok(transfers) => history
+                .entries
+                .extend(transfers.into_iter().map(|transfer| SolTransfer {
+                    memo: transfer.memo.or_else(|| signature.memo.clone()),
+                    ..transfer
+                })),
+            Err(e) => history.skipped.push((signature, e)),
         }
-        std::process::exit(1);
     }
 
-    let response_text = signatures_response.text()?;
-    let signatures_rpc_response: RpcResponse<Vec<SolanaSignature>> =
-        serde_json::from_str(&response_text)?;
-
-    let mut table = Table::new();
-    table.add_row(row!["Tx ID", "Sender", "Receiver", "Amount", "Timestamp"]);
-
-    for signature in signatures_rpc_response.result {
-        let transaction_payload = serde_json::json!({
-            "jsonrpc": "2.0",
-            "id": 1,
-            "method": "getTransaction",
-            "params": [
-                signature.signature,
-                "jsonParsed"
-            ]
+    Ok(history)
+}
+
+/// Fetches the net SOL balance change of a given Solana address in each
+/// transaction of its history, newest first. Transactions that leave the
+/// balance unchanged are left out; those that can't be parsed are returned
+/// in `skipped`.
+pub async fn fetch_sol_balance_history(
+    rpc: &impl SolanaRpc,
+    solana_address: &str,
+    paging: &SignaturePaging,
+    options: &FetchOptions,
+) -> Result<ParsedHistory<SolBalanceDelta>> {
+    let mut history = ParsedHistory::default();
+
+    for (signature, json) in fetch_history(rpc, solana_address, paging, options).await? {
+        let delta = parse_history_transaction(&signature, &json).and_then(|transaction| {
+            SolBalanceDelta::from_transaction(&transaction, solana_address)
         });
+        match delta {
+            Ok(Some(delta)) if delta.change() != 0 => history.entries.push(SolBalanceDelta {
+                memo: delta.memo.or(signature.memo),
+                ..delta
+            }),
+            Ok(_) => {}
+            Err(e) => history.skipped.push((signature, e)),
+        }
+    }
 
-        let transaction_response = client
-            .post(solana_rpc_url)
-            .json(&transaction_payload)
-            .send()?;
-        let transaction_response_text = transaction_response.text()?;
-        let json: Value = serde_json::from_str(&transaction_response_text)?;
-
-        if is_sol_transfer(&json) {
-            match SolTransfer::from_json(&json) {
-               
-                Ok(sol_transfer) => {
-                    #[allow(deprecated)]
-                    let timestamp = Utc.timestamp(sol_transfer.timestamp as i64, 0);
-                    table.add_row(row!(
-                        sol_transfer.transaction_id[0..10], 
-                        sol_transfer.sender, 
-                        sol_transfer.receiver, 
-                        sol_transfer.amount as f64 / 1_000_000_000.0, 
-                        timestamp.to_string()
-                    ));
-                },
-                Err(e) => {
-                    eprintln!("Error parsing SOL transfer: {}", e);
-                }
-            }
+    Ok(history)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::rpc::MemoryRpc;
+    use crate::test_fixtures::{signature, system_instruction, system_transfer, transaction};
+    use serde_json::json;
+
+    /// Alice pays Bob, then Carol pays Alice and Bob in one transaction,
+    /// then Alice only changes a nonce authority.
+    fn wallet() -> MemoryRpc {
+        let mut rpc = MemoryRpc::new();
+        for (id, slot) in [("Sig3", 3), ("Sig2", 2), ("Sig1", 1)] {
+            rpc.add_signature("Alice", signature(id, slot, Some(slot as i64 * 100)));
         }
+
+        let mut sig1 = transaction(
+            "Sig1",
+            Some(100),
+            &["Alice", "Bob"],
+            vec![system_transfer("Alice", "Bob", 1_000)],
+        );
+        sig1["meta"]["preBalances"] = json!([10_000, 0]);
+        sig1["meta"]["postBalances"] = json!([4_000, 1_000]);
+        rpc.add_transaction("Sig1", sig1);
+
+        let mut sig2 = transaction(
+            "Sig2",
+            Some(200),
+            &["Carol", "Alice", "Bob"],
+            vec![
+                system_transfer("Carol", "Alice", 2_000),
+                system_transfer("Carol", "Bob", 3_000),
+            ],
+        );
+        sig2["meta"]["preBalances"] = json!([20_000, 4_000, 1_000]);
+        sig2["meta"]["postBalances"] = json!([10_000, 6_000, 4_000]);
+        rpc.add_transaction("Sig2", sig2);
+
+        let mut sig3 = transaction(
+            "Sig3",
+            Some(300),
+            &["Carol", "Alice", "Nonce"],
+            vec![system_instruction(
+                "authorizeNonce",
+                json!({ "nonceAccount": "Nonce", "nonceAuthority": "Alice", "newAuthorized": "Carol" }),
+            )],
+        );
+        sig3["meta"]["preBalances"] = json!([10_000, 6_000, 1_500]);
+        sig3["meta"]["postBalances"] = json!([5_000, 6_000, 1_500]);
+        rpc.add_transaction("Sig3", sig3);
+        rpc
+    }
+
+    #[tokio::test]
+    async fn fetches_sol_transfer_history() {
+        let transfers = fetch_sol_transfer_history(
+            &wallet(),
+            "Alice",
+            &SignaturePaging::default(),
+            &FetchOptions::default(),
+        )
+        .await
+        .unwrap()
+        .entries;
+
+        let summary: Vec<(&str, &str, &str, u64, usize)> = transfers
+            .iter()
+            .map(|transfer| {
+                (
+                    transfer.transaction_id.as_str(),
+                    transfer.sender.as_str(),
+                    transfer.receiver.as_str(),
+                    transfer.amount,
+                    transfer.instruction_index,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("Sig2", "Carol", "Alice", 2_000, 0),
+                ("Sig2", "Carol", "Bob", 3_000, 1),
+                ("Sig1", "Alice", "Bob", 1_000, 0),
+            ]
+        );
+        assert_eq!(transfers[2].timestamp, 100);
+    }
+
+    #[tokio::test]
+    async fn fetches_sol_balance_history() {
+        let deltas = fetch_sol_balance_history(
+            &wallet(),
+            "Alice",
+            &SignaturePaging::default(),
+            &FetchOptions::default(),
+        )
+        .await
+        .unwrap()
+        .entries;
+
+        // Sig3 leaves Alice's balance unchanged.
+        let changes: Vec<(&str, i128, Option<u64>)> = deltas
+            .iter()
+            .map(|delta| (delta.transaction_id.as_str(), delta.change(), delta.fee))
+            .collect();
+        assert_eq!(
+            changes,
+            [("Sig2", 2_000, None), ("Sig1", -6_000, Some(5000))]
+        );
     }
 
-    table.printstd();
+    #[tokio::test]
+    async fn transactions_without_block_time_use_the_resolved_one() {
+        let mut rpc = MemoryRpc::new();
+        rpc.add_signature("Alice", signature("Sig1", 7, None));
+        rpc.add_block_time(7, 1_700_000_000);
+        rpc.add_transaction(
+            "Sig1",
+            transaction(
+                "Sig1",
+                None,
+                &["Alice", "Bob"],
+                vec![system_transfer("Alice", "Bob", 5000)],
+            ),
+        );
+        let paging = SignaturePaging {
+            since: Some(0),
+            ..SignaturePaging::default()
+        };
 
-    Ok(())
+        let transfers =
+            fetch_sol_transfer_history(&rpc, "Alice", &paging, &FetchOptions::default())
+                .await
+                .unwrap()
+                .entries;
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].timestamp, 1_700_000_000);
+    }
+
+    #[tokio::test]
+    async fn returns_transactions_that_cannot_be_parsed() {
+        let mut rpc = MemoryRpc::new();
+        rpc.add_signature("Alice", signature("Sig2", 2, Some(200)));
+        rpc.add_signature("Alice", signature("Sig1", 1, None));
+        rpc.add_transaction("Sig2", json!({ "slot": 2 }));
+        // Without a block time from either the transaction or its signature
+        // the transfer has no timestamp.
+        rpc.add_transaction(
+            "Sig1",
+            transaction(
+                "Sig1",
+                None,
+                &["Alice", "Bob"],
+                vec![system_transfer("Alice", "Bob", 1_000)],
+            ),
+        );
+        let paging = SignaturePaging::default();
+
+        let history = fetch_sol_transfer_history(&rpc, "Alice", &paging, &FetchOptions::default())
+            .await
+            .unwrap();
+        assert!(history.entries.is_empty());
+        let skipped: Vec<&str> = history
+            .skipped
+            .iter()
+            .map(|(signature, error)| {
+                assert!(matches!(error, Error::Parse { .. }));
+                signature.signature.as_str()
+            })
+            .collect();
+        assert_eq!(skipped, ["Sig2", "Sig1"]);
+    }
 }
@@ -1,56 +1,314 @@
-use serde_json::Value;
+use std::fmt;
 
+use crate::error::{Error, Result};
+use crate::memo::transaction_memo;
+use crate::models::{Instruction, ParsedInfo, SolanaTransactionDetails, SystemInstruction};
+use crate::transaction::{account_keys, instructions, is_failed, timestamp, transaction_id};
+
+/// A native SOL movement parsed from a `jsonParsed` transaction.
 #[derive(Debug)]
 pub struct SolTransfer {
     pub transaction_id: String,
+    /// System instruction that moved the lamports.
+    pub kind: SolTransferKind,
+    /// Account debited by the instruction.
     pub sender: String,
     pub receiver: String,
+    /// Amount in lamports.
     pub amount: u64,
+    /// Block time in unix seconds.
     pub timestamp: u64,
+    /// Account that paid the transaction fee, which isn't necessarily the
+    /// sender for sponsored transactions.
+    pub fee_payer: String,
+    /// Index of the top-level instruction that made the transfer, directly
+    /// or through CPI.
+    pub instruction_index: usize,
+    /// 1 for a top-level transfer instruction, 2 and up for an inner one.
+    pub stack_height: u32,
+    /// Memo of the transaction, if any.
+    pub memo: Option<String>,
 }
 
 impl SolTransfer {
-    pub fn from_json(json: &Value) -> Result<Self, &'static str> {
-        let transaction_id = json.pointer("/result/transaction/signatures/0")
-            .and_then(|v| v.as_str())
-            .ok_or("transaction_id not found")?
-            .to_string();
-
-        let sender = json.pointer("/result/transaction/message/accountKeys/0/pubkey")
-            .and_then(|v| v.as_str())
-            .ok_or("sender not found")?
-            .to_string();
-
-        let receiver = json.pointer("/result/transaction/message/instructions/0/parsed/info/destination")
-            .and_then(|v| v.as_str())
-            .ok_or("receiver not found")?
-            .to_string();
-
-        let amount = json.pointer("/result/transaction/message/instructions/0/parsed/info/lamports")
-            .and_then(|v| v.as_u64())
-            .ok_or("amount not found")?;
-
-        let timestamp = json.pointer("/result/blockTime")
-            .and_then(|v| v.as_u64())
-            .ok_or("timestamp not found")?;
-
-        Ok(SolTransfer {
-            transaction_id,
-            sender,
-            receiver,
-            amount,
-            timestamp,
-        })
+    /// Parses every system program instruction of a transaction that moves
+    /// lamports, including inner instructions, in execution order. Failed
+    /// transactions moved nothing and have no transfers.
+    pub fn from_transaction(transaction: &SolanaTransactionDetails) -> Result<Vec<Self>> {
+        if is_failed(transaction) {
+            return Ok(Vec::new());
+        }
+        let transaction_id = transaction_id(transaction)?;
+        let timestamp = timestamp(transaction)?;
+        let fee_payer = account_keys(transaction)
+            .into_iter()
+            .next()
+            .ok_or_else(|| Error::parse("fee payer not found"))?;
+        let memo = transaction_memo(transaction);
+
+        let mut transfers = Vec::new();
+        for instruction_ref in instructions(transaction) {
+            let Some(system_instruction) = system_instruction(instruction_ref.instruction) else {
+                continue;
+            };
+            let (kind, sender, receiver, amount) = match system_instruction {
+                SystemInstruction::Transfer {
+                    source,
+                    destination,
+                    lamports,
+                } => (SolTransferKind::Transfer, source, destination, lamports),
+                SystemInstruction::TransferWithSeed {
+                    source,
+                    destination,
+                    lamports,
+                } => (
+                    SolTransferKind::TransferWithSeed,
+                    source,
+                    destination,
+                    lamports,
+                ),
+                SystemInstruction::CreateAccount {
+                    source,
+                    new_account,
+                    lamports,
+                } => (
+                    SolTransferKind::CreateAccount,
+                    source,
+                    new_account,
+                    lamports,
+                ),
+                SystemInstruction::CreateAccountWithSeed {
+                    source,
+                    new_account,
+                    lamports,
+                } => (
+                    SolTransferKind::CreateAccountWithSeed,
+                    source,
+                    new_account,
+                    lamports,
+                ),
+                SystemInstruction::WithdrawFromNonce {
+                    nonce_account,
+                    destination,
+                    lamports,
+                } => (
+                    SolTransferKind::WithdrawNonce,
+                    nonce_account,
+                    destination,
+                    lamports,
+                ),
+            };
+
+            transfers.push(SolTransfer {
+                transaction_id: transaction_id.clone(),
+                kind,
+                sender: sender.clone(),
+                receiver: receiver.clone(),
+                amount: *amount,
+                timestamp,
+                fee_payer: fee_payer.clone(),
+                instruction_index: instruction_ref.index,
+                stack_height: instruction_ref.stack_height,
+                memo: memo.clone(),
+            });
+        }
+
+        Ok(transfers)
+    }
+}
+
+/// System program instructions that move lamports between accounts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SolTransferKind {
+    Transfer,
+    TransferWithSeed,
+    /// Funds a new account with its initial lamports.
+    CreateAccount,
+    CreateAccountWithSeed,
+    /// Withdraws lamports from a durable nonce account.
+    WithdrawNonce,
+}
+
+impl fmt::Display for SolTransferKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SolTransferKind::Transfer => "transfer",
+            SolTransferKind::TransferWithSeed => "transferWithSeed",
+            SolTransferKind::CreateAccount => "createAccount",
+            SolTransferKind::CreateAccountWithSeed => "createAccountWithSeed",
+            SolTransferKind::WithdrawNonce => "withdrawNonce",
+        };
+        f.write_str(name)
     }
 }
 
-pub fn is_sol_transfer(json: &Value) -> bool {
-    json.pointer("/result/transaction/message/instructions")
-        .and_then(|v| v.as_array())
-        .map_or(false, |instructions| {
-            instructions.iter().any(|instruction| {
-                instruction.pointer("/parsed/info/lamports").is_some() &&
-                instruction.pointer("/parsed/info/destination").is_some()
+/// Returns the system program instruction that moves lamports, if
+/// `instruction` is one.
+fn system_instruction(instruction: &Instruction) -> Option<&SystemInstruction> {
+    match instruction {
+        Instruction::Parsed(parsed) => match &parsed.parsed {
+            ParsedInfo::System(system_instruction) => Some(system_instruction),
+            _ => None,
+        },
+        Instruction::PartiallyDecoded(_) => None,
+    }
+}
+
+/// Returns true if any instruction of a transaction, top-level or inner,
+/// moves lamports.
+pub fn is_sol_transfer(transaction: &SolanaTransactionDetails) -> bool {
+    instructions(transaction)
+        .iter()
+        .any(|instruction_ref| system_instruction(instruction_ref.instruction).is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{system_instruction, system_transfer, transaction};
+    use crate::transaction::parse_transaction;
+    use serde_json::{json, Value};
+
+    fn parse(json: &Value) -> Vec<SolTransfer> {
+        SolTransfer::from_transaction(&parse_transaction(json).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn parses_every_transfer_instruction() {
+        let json = transaction(
+            "Sig1",
+            Some(100),
+            &["Alice", "Bob", "Carol"],
+            vec![
+                system_transfer("Alice", "Bob", 1_000),
+                json!({ "programId": "Other1111", "accounts": [], "data": "", "stackHeight": null }),
+                system_transfer("Alice", "Carol", 2_000),
+            ],
+        );
+
+        let transfers = parse(&json);
+        let summary: Vec<(&str, u64, usize)> = transfers
+            .iter()
+            .map(|transfer| {
+                (
+                    transfer.receiver.as_str(),
+                    transfer.amount,
+                    transfer.instruction_index,
+                )
+            })
+            .collect();
+        assert_eq!(summary, [("Bob", 1_000, 0), ("Carol", 2_000, 2)]);
+    }
+
+    #[test]
+    fn failed_transactions_have_no_transfers() {
+        let mut json = transaction(
+            "Sig1",
+            Some(100),
+            &["Alice", "Bob"],
+            vec![system_transfer("Alice", "Bob", 1_000)],
+        );
+        json["meta"]["err"] = json!({ "InstructionError": [0, "Custom"] });
+        assert!(parse(&json).is_empty());
+    }
+
+    #[test]
+    fn sender_is_the_instruction_source() {
+        // Carol pays the fee for Alice's transfer to Bob.
+        let json = transaction(
+            "Sig1",
+            Some(100),
+            &["Carol", "Alice", "Bob"],
+            vec![system_transfer("Alice", "Bob", 1_000)],
+        );
+
+        let transfers = parse(&json);
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].sender, "Alice");
+        assert_eq!(transfers[0].receiver, "Bob");
+        assert_eq!(transfers[0].fee_payer, "Carol");
+    }
+
+    #[test]
+    fn includes_inner_transfers() {
+        let mut json = transaction(
+            "Sig1",
+            Some(100),
+            &["Alice", "Bob", "Vault", "Program1111"],
+            vec![
+                json!({ "programId": "Program1111", "accounts": [], "data": "", "stackHeight": null }),
+                system_transfer("Alice", "Bob", 1_000),
+            ],
+        );
+        let mut inner = system_transfer("Vault", "Alice", 7_000);
+        inner["stackHeight"] = json!(2);
+        json["meta"]["innerInstructions"] = json!([{ "index": 0, "instructions": [inner] }]);
+
+        let transfers = parse(&json);
+        let summary: Vec<(&str, &str, usize, u32)> = transfers
+            .iter()
+            .map(|transfer| {
+                (
+                    transfer.sender.as_str(),
+                    transfer.receiver.as_str(),
+                    transfer.instruction_index,
+                    transfer.stack_height,
+                )
+            })
+            .collect();
+        assert_eq!(summary, [("Vault", "Alice", 0, 2), ("Alice", "Bob", 1, 1)]);
+    }
+
+    #[test]
+    fn classifies_lamport_moving_system_instructions() {
+        let json = transaction(
+            "Sig1",
+            Some(100),
+            &["Alice", "New", "Seeded", "Nonce", "Bob"],
+            vec![
+                system_instruction(
+                    "createAccount",
+                    json!({ "source": "Alice", "newAccount": "New", "lamports": 1, "space": 0, "owner": "Program1111" }),
+                ),
+                system_instruction(
+                    "createAccountWithSeed",
+                    json!({ "source": "Alice", "newAccount": "Seeded", "base": "Alice", "seed": "s", "lamports": 2, "space": 0, "owner": "Program1111" }),
+                ),
+                system_instruction(
+                    "transferWithSeed",
+                    json!({ "source": "Seeded", "sourceBase": "Alice", "sourceSeed": "s", "sourceOwner": "Program1111", "destination": "Bob", "lamports": 3 }),
+                ),
+                system_instruction(
+                    "withdrawFromNonce",
+                    json!({ "nonceAccount": "Nonce", "destination": "Bob", "recentBlockhashesSysvar": "Sysvar1111", "rentSysvar": "Rent1111", "nonceAuthority": "Alice", "lamports": 4 }),
+                ),
+                system_instruction(
+                    "assign",
+                    json!({ "account": "New", "owner": "Program1111" }),
+                ),
+            ],
+        );
+
+        let transfers = parse(&json);
+        let summary: Vec<(SolTransferKind, &str, &str, u64)> = transfers
+            .iter()
+            .map(|transfer| {
+                (
+                    transfer.kind,
+                    transfer.sender.as_str(),
+                    transfer.receiver.as_str(),
+                    transfer.amount,
+                )
             })
-        })
-}
\ No newline at end of file
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (SolTransferKind::CreateAccount, "Alice", "New", 1),
+                (SolTransferKind::CreateAccountWithSeed, "Alice", "Seeded", 2),
+                (SolTransferKind::TransferWithSeed, "Seeded", "Bob", 3),
+                (SolTransferKind::WithdrawNonce, "Nonce", "Bob", 4),
+            ]
+        );
+    }
+}
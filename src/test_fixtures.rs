@@ -0,0 +1,133 @@
+//! Builders for the `getSignaturesForAddress` and `jsonParsed`
+//! `getTransaction` results the unit tests feed through `MemoryRpc`.
+
+use serde_json::{json, Value};
+
+use crate::models::SolanaSignature;
+
+pub const SYSTEM_PROGRAM: &str = "11111111111111111111111111111111";
+pub const TOKEN_PROGRAM: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+
+pub fn signature(id: &str, slot: u64, block_time: Option<i64>) -> SolanaSignature {
+    serde_json::from_value(json!({
+        "signature": id,
+        "slot": slot,
+        "blockTime": block_time,
+        "confirmationStatus": "finalized",
+        "err": null,
+        "memo": null
+    }))
+    .unwrap()
+}
+
+/// A successful transaction `id` over `keys`, paid for by `keys[0]`. All
+/// SOL balances are zero and there are no token balances or inner
+/// instructions; tests set the `meta` fields they need.
+pub fn transaction(
+    id: &str,
+    block_time: Option<i64>,
+    keys: &[&str],
+    instructions: Vec<Value>,
+) -> Value {
+    let account_keys: Vec<Value> = keys
+        .iter()
+        .enumerate()
+        .map(|(index, key)| {
+            json!({
+                "pubkey": key,
+                "signer": index == 0,
+                "writable": true,
+                "source": "transaction"
+            })
+        })
+        .collect();
+    json!({
+        "slot": 1,
+        "blockTime": block_time,
+        "version": 0,
+        "meta": {
+            "err": null,
+            "fee": 5000,
+            "preBalances": vec![0; keys.len()],
+            "postBalances": vec![0; keys.len()],
+            "innerInstructions": [],
+            "logMessages": [],
+            "preTokenBalances": [],
+            "postTokenBalances": [],
+            "rewards": [],
+            "loadedAddresses": { "writable": [], "readonly": [] }
+        },
+        "transaction": {
+            "signatures": [id],
+            "message": {
+                "accountKeys": account_keys,
+                "recentBlockhash": "Blockhash1111",
+                "instructions": instructions
+            }
+        }
+    })
+}
+
+/// A parsed system program instruction.
+pub fn system_instruction(instruction_type: &str, info: Value) -> Value {
+    json!({
+        "program": "system",
+        "programId": SYSTEM_PROGRAM,
+        "parsed": { "type": instruction_type, "info": info },
+        "stackHeight": null
+    })
+}
+
+pub fn system_transfer(source: &str, destination: &str, lamports: u64) -> Value {
+    system_instruction(
+        "transfer",
+        json!({ "source": source, "destination": destination, "lamports": lamports }),
+    )
+}
+
+/// A parsed SPL Token instruction.
+pub fn token_instruction(instruction_type: &str, info: Value) -> Value {
+    json!({
+        "program": "spl-token",
+        "programId": TOKEN_PROGRAM,
+        "parsed": { "type": instruction_type, "info": info },
+        "stackHeight": null
+    })
+}
+
+/// A parsed SPL Memo instruction carrying `text`.
+pub fn memo(text: &str) -> Value {
+    json!({
+        "program": "spl-memo",
+        "programId": "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr",
+        "parsed": text,
+        "stackHeight": null
+    })
+}
+
+/// A `uiTokenAmount` of `amount` base units.
+pub fn token_amount(amount: u64, decimals: u8) -> Value {
+    json!({
+        "amount": amount.to_string(),
+        "decimals": decimals,
+        "uiAmount": null,
+        "uiAmountString": crate::amount::format_units(amount as i128, decimals)
+    })
+}
+
+/// A `preTokenBalances` or `postTokenBalances` entry.
+pub fn token_balance(
+    account_index: usize,
+    mint: &str,
+    owner: Option<&str>,
+    amount: u64,
+    decimals: u8,
+) -> Value {
+    json!({
+        "accountIndex": account_index,
+        "mint": mint,
+        "owner": owner,
+        "programId": TOKEN_PROGRAM,
+        "uiTokenAmount": token_amount(amount, decimals)
+    })
+}
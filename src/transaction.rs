@@ -0,0 +1,367 @@
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::decode::decode_transaction;
+use crate::error::{Error, Result};
+use crate::models::{Instruction, SolanaTransactionDetails, TokenBalance};
+
+/// Deserializes a `getTransaction` result. `jsonParsed` results are read
+/// as they are; binary-encoded ones are decoded locally.
+pub fn parse_transaction(json: &Value) -> Result<SolanaTransactionDetails> {
+    // Binary encodings send the transaction as `[data, encoding]`.
+    if json.get("transaction").is_some_and(Value::is_array) {
+        return decode_transaction(json);
+    }
+    SolanaTransactionDetails::deserialize(json).map_err(|error| Error::Parse {
+        message: "unexpected getTransaction result".to_string(),
+        source: Some(error),
+    })
+}
+
+/// Returns the first signature, which identifies the transaction.
+pub fn transaction_id(transaction: &SolanaTransactionDetails) -> Result<String> {
+    transaction
+        .transaction
+        .signatures
+        .first()
+        .cloned()
+        .ok_or_else(|| Error::parse("transaction_id not found"))
+}
+
+/// Returns the block time in unix seconds.
+pub fn timestamp(transaction: &SolanaTransactionDetails) -> Result<u64> {
+    transaction
+        .block_time
+        .and_then(|block_time| u64::try_from(block_time).ok())
+        .ok_or_else(|| Error::parse("timestamp not found"))
+}
+
+/// Returns true if the transaction failed. Its instructions are still
+/// listed, but none of their effects apply apart from the fee.
+pub fn is_failed(transaction: &SolanaTransactionDetails) -> bool {
+    transaction
+        .meta
+        .as_ref()
+        .is_some_and(|meta| meta.err.is_some())
+}
+
+/// Returns every account key of a transaction in index order: the message's
+/// static keys followed by the writable and then readonly addresses loaded
+/// from address lookup tables.
+///
+/// `jsonParsed` messages already list loaded addresses (with `"source":
+/// "lookupTable"`), so `meta.loadedAddresses` is only appended when the
+/// message doesn't include them.
+pub fn account_keys(transaction: &SolanaTransactionDetails) -> Vec<String> {
+    let message_keys = &transaction.transaction.message.account_keys;
+    let mut keys: Vec<String> = message_keys.iter().map(|key| key.pubkey.clone()).collect();
+
+    let includes_loaded = message_keys
+        .iter()
+        .any(|key| key.source.as_deref() == Some("lookupTable"));
+    if !includes_loaded {
+        if let Some(meta) = &transaction.meta {
+            keys.extend(meta.loaded_addresses.writable.iter().cloned());
+            keys.extend(meta.loaded_addresses.readonly.iter().cloned());
+        }
+    }
+
+    keys
+}
+
+/// An instruction of a transaction together with its position in the
+/// transaction.
+#[derive(Debug, Clone, Copy)]
+pub struct InstructionRef<'a> {
+    pub instruction: &'a Instruction,
+    /// Index of the top-level instruction, or of the top-level instruction
+    /// that invoked it for inner instructions.
+    pub index: usize,
+    /// Invocation depth: 1 for top-level instructions, 2 and up for
+    /// instructions invoked through CPI.
+    pub stack_height: u32,
+}
+
+/// Returns every instruction of a transaction in execution order: each
+/// top-level instruction followed by the inner instructions it invoked, as
+/// recorded in `meta.innerInstructions`.
+pub fn instructions(transaction: &SolanaTransactionDetails) -> Vec<InstructionRef<'_>> {
+    let inner = transaction
+        .meta
+        .as_ref()
+        .map(|meta| meta.inner_instructions.as_slice())
+        .unwrap_or_default();
+
+    let mut all = Vec::new();
+    for (index, instruction) in transaction
+        .transaction
+        .message
+        .instructions
+        .iter()
+        .enumerate()
+    {
+        all.push(InstructionRef {
+            instruction,
+            index,
+            stack_height: 1,
+        });
+
+        let invoked = inner
+            .iter()
+            .filter(|group| group.index == index)
+            .flat_map(|group| &group.instructions);
+        for instruction in invoked {
+            all.push(InstructionRef {
+                instruction,
+                index,
+                // Nodes older than 1.14 don't report stackHeight.
+                stack_height: instruction.stack_height().unwrap_or(2),
+            });
+        }
+    }
+    all
+}
+
+/// Returns the token balances recorded in `meta.preTokenBalances` and
+/// `meta.postTokenBalances`, keyed by token account. Where an account has
+/// both, the post balance wins; mint, owner and decimals are the same in
+/// either.
+pub fn token_accounts(transaction: &SolanaTransactionDetails) -> HashMap<String, TokenBalance> {
+    let Some(meta) = &transaction.meta else {
+        return HashMap::new();
+    };
+    let keys = account_keys(transaction);
+    meta.pre_token_balances
+        .iter()
+        .chain(&meta.post_token_balances)
+        .filter_map(|balance| {
+            let account = keys.get(balance.account_index)?;
+            Some((account.clone(), balance.clone()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ParsedInfo, SystemInstruction, TokenInstruction, TransactionVersion};
+
+    /// A `jsonParsed` v0 transaction: a SOL transfer and a token transfer
+    /// whose destination and mint come from a lookup table, plus an inner
+    /// token transfer and a memo.
+    fn v0_fixture() -> Value {
+        serde_json::json!({
+            "slot": 250000000,
+            "blockTime": 1700000000,
+            "version": 0,
+            "meta": {
+                "err": null,
+                "fee": 5000,
+                "preBalances": [1000000000, 0, 2039280, 1, 1, 1, 2039280, 1461600],
+                "postBalances": [999990000, 5000, 2039280, 1, 1, 1, 2039280, 1461600],
+                "innerInstructions": [{
+                    "index": 1,
+                    "instructions": [{
+                        "program": "spl-token",
+                        "programId": "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+                        "parsed": {
+                            "type": "transfer",
+                            "info": {
+                                "source": "SrcAcct",
+                                "destination": "DstAcct",
+                                "authority": "Payer",
+                                "amount": "9"
+                            }
+                        },
+                        "stackHeight": 2
+                    }]
+                }],
+                "logMessages": [],
+                "preTokenBalances": [{
+                    "accountIndex": 2,
+                    "mint": "MintA",
+                    "owner": "Payer",
+                    "programId": "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+                    "uiTokenAmount": {
+                        "amount": "5000000",
+                        "decimals": 6,
+                        "uiAmount": 5.0,
+                        "uiAmountString": "5"
+                    }
+                }],
+                "postTokenBalances": [{
+                    "accountIndex": 6,
+                    "mint": "MintA",
+                    "owner": "Bob",
+                    "programId": "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+                    "uiTokenAmount": {
+                        "amount": "1500009",
+                        "decimals": 6,
+                        "uiAmount": 1.500009,
+                        "uiAmountString": "1.500009"
+                    }
+                }],
+                "rewards": [],
+                "loadedAddresses": {
+                    "writable": ["DstAcct"],
+                    "readonly": ["MintA"]
+                }
+            },
+            "transaction": {
+                "signatures": ["Sig1111"],
+                "message": {
+                    "accountKeys": [
+                        { "pubkey": "Payer", "signer": true, "writable": true, "source": "transaction" },
+                        { "pubkey": "Bob", "signer": false, "writable": true, "source": "transaction" },
+                        { "pubkey": "SrcAcct", "signer": false, "writable": true, "source": "transaction" },
+                        { "pubkey": "11111111111111111111111111111111", "signer": false, "writable": false, "source": "transaction" },
+                        { "pubkey": "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA", "signer": false, "writable": false, "source": "transaction" },
+                        { "pubkey": "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr", "signer": false, "writable": false, "source": "transaction" },
+                        { "pubkey": "DstAcct", "signer": false, "writable": true, "source": "lookupTable" },
+                        { "pubkey": "MintA", "signer": false, "writable": false, "source": "lookupTable" }
+                    ],
+                    "recentBlockhash": "Blockhash1111",
+                    "instructions": [
+                        {
+                            "program": "system",
+                            "programId": "11111111111111111111111111111111",
+                            "parsed": {
+                                "type": "transfer",
+                                "info": { "source": "Payer", "destination": "Bob", "lamports": 5000 }
+                            },
+                            "stackHeight": null
+                        },
+                        {
+                            "program": "spl-token",
+                            "programId": "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+                            "parsed": {
+                                "type": "transferChecked",
+                                "info": {
+                                    "source": "SrcAcct",
+                                    "destination": "DstAcct",
+                                    "mint": "MintA",
+                                    "authority": "Payer",
+                                    "tokenAmount": {
+                                        "amount": "1500000",
+                                        "decimals": 6,
+                                        "uiAmount": 1.5,
+                                        "uiAmountString": "1.5"
+                                    }
+                                }
+                            },
+                            "stackHeight": null
+                        },
+                        {
+                            "program": "spl-memo",
+                            "programId": "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr",
+                            "parsed": "INV-42",
+                            "stackHeight": null
+                        },
+                        {
+                            "programId": "Other1111",
+                            "accounts": ["Payer"],
+                            "data": "3Bxs",
+                            "stackHeight": null
+                        }
+                    ],
+                    "addressTableLookups": [{
+                        "accountKey": "Table1111",
+                        "writableIndexes": [0],
+                        "readonlyIndexes": [1]
+                    }]
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn parses_json_parsed_v0_transaction() {
+        let transaction = parse_transaction(&v0_fixture()).unwrap();
+
+        assert!(matches!(
+            transaction.version,
+            Some(TransactionVersion::Number(0))
+        ));
+        assert_eq!(transaction_id(&transaction).unwrap(), "Sig1111");
+        assert_eq!(timestamp(&transaction).unwrap(), 1700000000);
+        assert!(!is_failed(&transaction));
+
+        // Loaded addresses are already in the message and aren't repeated.
+        let keys = account_keys(&transaction);
+        assert_eq!(keys.len(), 8);
+        assert_eq!(keys[6], "DstAcct");
+        assert_eq!(keys[7], "MintA");
+
+        let instructions = instructions(&transaction);
+        let positions: Vec<(usize, u32)> = instructions
+            .iter()
+            .map(|instruction_ref| (instruction_ref.index, instruction_ref.stack_height))
+            .collect();
+        assert_eq!(positions, vec![(0, 1), (1, 1), (1, 2), (2, 1), (3, 1)]);
+
+        let parsed = |position: usize| match instructions[position].instruction {
+            Instruction::Parsed(parsed) => &parsed.parsed,
+            Instruction::PartiallyDecoded(_) => panic!("instruction {} not parsed", position),
+        };
+        assert!(matches!(
+            parsed(0),
+            ParsedInfo::System(SystemInstruction::Transfer { lamports: 5000, .. })
+        ));
+        match parsed(1) {
+            ParsedInfo::Token(TokenInstruction::TransferChecked {
+                destination,
+                mint,
+                token_amount,
+                ..
+            }) => {
+                assert_eq!(destination, "DstAcct");
+                assert_eq!(mint, "MintA");
+                assert_eq!(token_amount.raw_amount(), Some(1500000));
+            }
+            other => panic!("unexpected instruction {:?}", other),
+        }
+        assert!(matches!(
+            parsed(2),
+            ParsedInfo::Token(TokenInstruction::Transfer { amount: 9, .. })
+        ));
+        assert!(matches!(parsed(3), ParsedInfo::Memo(memo) if memo == "INV-42"));
+        assert_eq!(instructions[4].instruction.program_id(), "Other1111");
+
+        let token_accounts = token_accounts(&transaction);
+        assert_eq!(token_accounts["SrcAcct"].owner.as_deref(), Some("Payer"));
+        assert_eq!(token_accounts["DstAcct"].owner.as_deref(), Some("Bob"));
+    }
+
+    #[test]
+    fn appends_loaded_addresses_missing_from_message() {
+        let mut json = v0_fixture();
+        let keys = json["transaction"]["message"]["accountKeys"]
+            .as_array_mut()
+            .unwrap();
+        keys.truncate(6);
+        for key in keys.iter_mut() {
+            key.as_object_mut().unwrap().remove("source");
+        }
+        let transaction = parse_transaction(&json).unwrap();
+        assert_eq!(account_keys(&transaction)[6..], ["DstAcct", "MintA"]);
+    }
+
+    #[test]
+    fn flags_failed_transactions() {
+        let mut json = v0_fixture();
+        json["meta"]["err"] = serde_json::json!({ "InstructionError": [1, "Custom"] });
+        let transaction = parse_transaction(&json).unwrap();
+        assert!(is_failed(&transaction));
+    }
+
+    #[test]
+    fn rejects_results_that_do_not_fit_the_model() {
+        let mut json = v0_fixture();
+        json["transaction"]["message"]
+            .as_object_mut()
+            .unwrap()
+            .remove("accountKeys");
+        assert!(matches!(parse_transaction(&json), Err(Error::Parse { .. })));
+    }
+}